@@ -0,0 +1,134 @@
+//! Deliberately weak searchers, used as calibration opponents when measuring Elo.
+//!
+//! None of these look at the search limits: they all answer instantly.
+
+use crate::rng::Rng;
+use crate::rules::{Move, State};
+use crate::search::{evaluate, EvalParams, Evaluation, SearchLimits, Searcher, StopHandle};
+
+/// The state every baseline needs, and a helper for picking among equally good moves.
+struct Baseline {
+  rng:     Rng,
+  state:   State,
+  do_stop: StopHandle,
+}
+
+impl Baseline {
+  fn new(seed: u64) -> Baseline {
+    Baseline {
+      rng:     Rng::new(seed),
+      state:   State::new(),
+      do_stop: StopHandle::new(),
+    }
+  }
+
+  /// Picks uniformly among the moves with the highest score.
+  fn pick_best(&self, score: impl Fn(Move) -> Evaluation) -> (Evaluation, Option<Move>) {
+    let mut moves = Vec::new();
+    self.state.move_gen(&mut moves);
+    let scores = moves.iter().map(|m| score(*m)).collect::<Vec<_>>();
+    let best_score = *scores.iter().max().unwrap();
+    let best_moves = moves
+      .iter()
+      .zip(&scores)
+      .filter(|(_, s)| **s == best_score)
+      .map(|(m, _)| *m)
+      .collect::<Vec<_>>();
+    let m = best_moves[self.rng.generate_range(best_moves.len() as u32) as usize];
+    (best_score, Some(m))
+  }
+}
+
+macro_rules! impl_baseline_searcher {
+  ($t:ty, $name:expr) => {
+    impl Searcher for $t {
+      fn name(&self) -> &'static str {
+        $name
+      }
+
+      fn state(&self) -> &State {
+        &self.baseline.state
+      }
+
+      fn set_position(&mut self, state: State) {
+        self.baseline.state = state;
+      }
+
+      fn make_move(&mut self, m: Move) -> Result<(), &'static str> {
+        self.baseline.state.make_move(m)
+      }
+
+      fn search(&mut self, _limits: &SearchLimits) -> (Evaluation, Option<Move>) {
+        self.pick()
+      }
+
+      fn stop_handle(&self) -> StopHandle {
+        self.baseline.do_stop.clone()
+      }
+    }
+  };
+}
+
+/// Plays uniformly random legal moves.
+pub struct RandomMover {
+  baseline: Baseline,
+}
+
+impl RandomMover {
+  pub fn new(seed: u64) -> RandomMover {
+    RandomMover {
+      baseline: Baseline::new(seed),
+    }
+  }
+
+  fn pick(&self) -> (Evaluation, Option<Move>) {
+    self.baseline.pick_best(|_| 0)
+  }
+}
+
+impl_baseline_searcher!(RandomMover, "random");
+
+/// Plays the move that gains the most stones right now.
+pub struct GreedyMover {
+  baseline: Baseline,
+}
+
+impl GreedyMover {
+  pub fn new(seed: u64) -> GreedyMover {
+    GreedyMover {
+      baseline: Baseline::new(seed),
+    }
+  }
+
+  fn pick(&self) -> (Evaluation, Option<Move>) {
+    self.baseline.pick_best(|m| 100 * self.baseline.state.stone_gain(m) as Evaluation)
+  }
+}
+
+impl_baseline_searcher!(GreedyMover, "greedy");
+
+/// Plays the move with the best static evaluation after it, without looking at replies.
+pub struct OnePlyMinimax {
+  baseline:        Baseline,
+  pub eval_params: EvalParams,
+}
+
+impl OnePlyMinimax {
+  pub fn new(seed: u64) -> OnePlyMinimax {
+    OnePlyMinimax {
+      baseline:    Baseline::new(seed),
+      eval_params: EvalParams::default(),
+    }
+  }
+
+  fn pick(&self) -> (Evaluation, Option<Move>) {
+    self.baseline.pick_best(|m| {
+      let mut state = self.baseline.state.clone();
+      state.make_move(m).unwrap();
+      // The evaluation is from the opponent's point of view after our move.
+      -evaluate(&self.eval_params, &state)
+    })
+  }
+}
+
+impl_baseline_searcher!(OnePlyMinimax, "minimax1");
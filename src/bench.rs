@@ -0,0 +1,55 @@
+//! A fixed search benchmark. Its node count is a signature of the search, so any change to it
+//! should be deliberate, and its speed is what testing frameworks scale time controls by.
+
+use std::time::Instant;
+
+use crate::rules::State;
+use crate::search::Engine;
+
+pub const DEFAULT_BENCH_DEPTH: u16 = 5;
+
+/// Positions from self-play games on the usual gap layouts, from the opening to the endgame.
+pub const BENCH_FENS: [&str; 14] = [
+  "x5o/7/7/7/7/7/o5x x 0 1",
+  "xxoo2o/3oo2/7/7/7/7/o5x x 0 1",
+  "1xx3o/1ooooo1/1oooxx1/4x2/7/7/o5x x 0 1",
+  "ooxxxxx/ooxxx2/1oooxo1/2ooxo1/7/7/o5x x 0 1",
+  "ooxooxx/xxxoooo/xxxxxoo/1x1xxoo/7/7/o5x x 0 1",
+  "ooxooxx/xxxooxx/1ooooxx/1ooxxxx/1ooxoo1/4o1x/6x x 0 1",
+  "oooooxx/xxoooxx/oxxxooo/ooooxxo/xoo1xxo/1oo1xxx/3ooox x 0 1",
+  "xxxoo1o/5o1/2-1-2/7/2-1-2/7/oo4x x 0 1",
+  "1ooxxxx/1ooxooo/2-x-o1/2xxo2/2-x-2/2xx3/o5x x 0 1",
+  "oooxxx1/ooxxxxx/oo-1-oo/1o2ooo/2-o-ox/2xxoxx/o1xxoxx x 0 1",
+  "x2oo1o/x1o2o1/1x1-3/2-1-2/3-3/7/o5x x 0 1",
+  "xxxx1oo/xxxx1xx/ooo-1oo/1o-o-oo/2o-3/2oo3/7 x 0 1",
+  "xxxo1o1/xxxoooo/xxx-ooo/1x-o-ox/1oo-xxx/ooooxx1/7 x 0 1",
+  "xoooxx1/xoooxxo/o2-ooo/xo-o-ox/xxx-xxx/xooo1x1/xooo3 x 0 1",
+];
+
+pub struct BenchResult {
+  pub nodes:   u64,
+  pub time_ms: u64,
+}
+
+impl BenchResult {
+  pub fn nodes_per_second(&self) -> u64 {
+    self.nodes * 1000 / self.time_ms.max(1)
+  }
+}
+
+/// Searches every bench position to `depth` with a fresh single threaded engine, so the node
+/// count only depends on the search itself.
+pub fn run_bench(depth: u16) -> BenchResult {
+  let start = Instant::now();
+  let mut nodes = 0;
+  for fen in BENCH_FENS {
+    let mut engine = Engine::new(0);
+    engine.set_position(State::from_fen(fen).unwrap());
+    engine.run_depth(depth);
+    nodes += engine.nodes();
+  }
+  BenchResult {
+    nodes,
+    time_ms: start.elapsed().as_millis() as u64,
+  }
+}
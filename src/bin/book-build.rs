@@ -0,0 +1,90 @@
+//! Builds an opening book from PGN games.
+//!
+//! Usage: book-build <output file> <pgn file>... [--depth N] [--min-games N]
+//!
+//! Every move in the first `--depth` plies of each finished game is counted, and moves played in
+//! fewer than `--min-games` games are left out. Moves are weighted by the points they scored.
+
+use snpataxx::book::BookBuilder;
+use snpataxx::game::GameRecord;
+
+const USAGE: &str = "Usage: book-build <output file> <pgn file>... [--depth N] [--min-games N]";
+
+struct Config {
+  output:    String,
+  pgns:      Vec<String>,
+  depth:     usize,
+  min_games: u32,
+}
+
+fn parse_args() -> Result<Config, String> {
+  let mut config = Config {
+    output:    String::new(),
+    pgns:      Vec::new(),
+    depth:     12,
+    min_games: 2,
+  };
+  let args = std::env::args().skip(1).collect::<Vec<_>>();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let mut value = || {
+      let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+      value.parse::<u32>().map_err(|_| format!("Invalid number: {}", value))
+    };
+    match arg.as_str() {
+      "--depth" => config.depth = value()? as usize,
+      "--min-games" => config.min_games = value()?,
+      arg if arg.starts_with("--") => return Err(format!("Unknown flag: {}", arg)),
+      arg if config.output.is_empty() => config.output = arg.to_string(),
+      arg => config.pgns.push(arg.to_string()),
+    }
+  }
+  if config.output.is_empty() {
+    return Err("Missing output file".to_string());
+  }
+  if config.pgns.is_empty() {
+    return Err("Missing PGN files".to_string());
+  }
+  Ok(config)
+}
+
+fn build(config: &Config) -> Result<(), String> {
+  let mut builder = BookBuilder::new(config.depth);
+  let (mut games, mut skipped) = (0, 0);
+  for path in &config.pgns {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    for (i, game) in
+      GameRecord::parse_all(&text).map_err(|e| format!("{}: {}", path, e))?.iter().enumerate()
+    {
+      match builder.add_game(game) {
+        Ok(true) => games += 1,
+        Ok(false) => skipped += 1,
+        Err(e) => {
+          eprintln!("{}: skipping game {}: {}", path, i + 1, e);
+          skipped += 1;
+        }
+      }
+    }
+  }
+  let book = builder.build(config.min_games);
+  book.save(&config.output)?;
+  println!(
+    "{} games, {} skipped, {} book moves",
+    games,
+    skipped,
+    book.len()
+  );
+  Ok(())
+}
+
+fn main() {
+  let config = parse_args().unwrap_or_else(|e| {
+    eprintln!("{}", e);
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+  });
+  if let Err(e) = build(&config) {
+    eprintln!("{}", e);
+    std::process::exit(1);
+  }
+}
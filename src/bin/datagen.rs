@@ -0,0 +1,167 @@
+//! Self-play data generation.
+//!
+//! Usage: datagen <output file> [--games N] [--threads N] [--depth N | --nodes N]
+//!                [--random-plies N] [--openings <file>] [--seed N]
+//!
+//! Games start from the openings file in turn, one FEN or EPD per line, or from the start position
+//! without one. The random plies are played on top of the opening.
+//!
+//! Every position after the random opening is written as a `PackedPosition`, with its search score
+//! and the result of the game it came from.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+use snpataxx::data::PackedPosition;
+use snpataxx::openings;
+use snpataxx::rng::Rng;
+use snpataxx::rules::{GameResult, State};
+use snpataxx::search::Engine;
+
+/// Games that run this long are adjudicated by counting stones, since stones can jump forever.
+const MAX_GAME_PLIES: usize = 400;
+
+enum Limit {
+  Depth(u16),
+  Nodes(u64),
+}
+
+struct Config {
+  output:       String,
+  games:        usize,
+  threads:      usize,
+  limit:        Limit,
+  random_plies: usize,
+  openings:     Vec<State>,
+  seed:         u64,
+}
+
+fn parse_args() -> Result<Config, String> {
+  let args = std::env::args().skip(1).collect::<Vec<_>>();
+  let mut config = Config {
+    output:       String::new(),
+    games:        1000,
+    threads:      std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+    limit:        Limit::Depth(3),
+    random_plies: 4,
+    openings:     vec![State::startpos()],
+    seed:         rand::random(),
+  };
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let mut value = || {
+      let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+      value.parse::<u64>().map_err(|_| format!("Invalid number: {}", value))
+    };
+    match arg.as_str() {
+      "--games" => config.games = value()? as usize,
+      "--threads" => config.threads = value()?.max(1) as usize,
+      "--depth" => config.limit = Limit::Depth(value()? as u16),
+      "--nodes" => config.limit = Limit::Nodes(value()?),
+      "--random-plies" => config.random_plies = value()? as usize,
+      "--openings" => {
+        let path = args.next().ok_or("Missing value for --openings")?;
+        config.openings = openings::load(path)?;
+      }
+      "--seed" => config.seed = value()?,
+      arg if arg.starts_with("--") => return Err(format!("Unknown flag: {}", arg)),
+      arg => config.output = arg.to_string(),
+    }
+  }
+  if config.output.is_empty() {
+    return Err("Missing output file".to_string());
+  }
+  Ok(config)
+}
+
+fn play_game(config: &Config, game_index: u64) -> Vec<PackedPosition> {
+  let seed = config.seed.wrapping_add(game_index);
+  let rng = Rng::new(seed);
+  let mut engine = Engine::new(seed);
+  let opening = &config.openings[game_index as usize % config.openings.len()];
+  engine.set_position(opening.clone());
+  let mut positions = Vec::new();
+  let mut moves = Vec::new();
+  for ply in 0..MAX_GAME_PLIES {
+    if engine.state.game_is_over() {
+      break;
+    }
+    let m = match ply < config.random_plies {
+      true => {
+        moves.clear();
+        engine.state.move_gen(&mut moves);
+        moves[rng.generate_range(moves.len() as u32) as usize]
+      }
+      false => {
+        let (score, m) = match config.limit {
+          Limit::Depth(depth) => engine.run_depth(depth),
+          Limit::Nodes(nodes) => engine.run_nodes(nodes),
+        };
+        positions.push(PackedPosition {
+          state:  engine.state.clone(),
+          score:  score.clamp(i16::MIN as i32 + 1, i16::MAX as i32) as i16,
+          // Filled in once the game is over.
+          result: GameResult::Draw,
+        });
+        m.unwrap()
+      }
+    };
+    engine.make_move(m).unwrap();
+  }
+  let result = engine.state.result_by_material();
+  for position in &mut positions {
+    position.result = result;
+  }
+  positions
+}
+
+fn main() {
+  let config = parse_args().unwrap_or_else(|e| {
+    eprintln!("{}", e);
+    eprintln!(
+      "Usage: datagen <output file> [--games N] [--threads N] [--depth N | --nodes N] \
+       [--random-plies N] [--openings <file>] [--seed N]"
+    );
+    std::process::exit(1);
+  });
+  println!(
+    "Generating {} games with seed {}",
+    config.games, config.seed
+  );
+
+  let file =
+    std::fs::File::create(&config.output).unwrap_or_else(|e| panic!("{}: {}", config.output, e));
+  let mut writer = std::io::BufWriter::new(file);
+  let next_game = AtomicUsize::new(0);
+  let (sender, receiver) = mpsc::channel::<Vec<PackedPosition>>();
+
+  std::thread::scope(|scope| {
+    for _ in 0..config.threads {
+      let sender = sender.clone();
+      let (config, next_game) = (&config, &next_game);
+      scope.spawn(move || loop {
+        let game_index = next_game.fetch_add(1, Ordering::Relaxed);
+        if game_index >= config.games {
+          break;
+        }
+        sender.send(play_game(config, game_index as u64)).unwrap();
+      });
+    }
+    // Drop our own sender so the loop below ends when the workers are done.
+    drop(sender);
+
+    let (mut games, mut positions) = (0, 0);
+    for game in receiver {
+      for position in &game {
+        position.write_to(&mut writer).unwrap();
+      }
+      games += 1;
+      positions += game.len();
+      if games % 100 == 0 || games == config.games {
+        println!("{} games, {} positions", games, positions);
+      }
+    }
+  });
+  writer.flush().unwrap();
+}
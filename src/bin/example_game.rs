@@ -2,8 +2,10 @@ use snpataxx::rules::State;
 use snpataxx::search::Engine;
 
 fn main() {
-  let mut engine = Engine::new(rand::random());
-  engine.set_position(State::from_fen("x5o/7/7/7/7/7/o5x x 0 1").unwrap());
+  let seed = rand::random();
+  println!("seed {}", seed);
+  let mut engine = Engine::new(seed);
+  engine.set_position(State::startpos());
   loop {
     println!("{}", engine.state.render());
     let (eval, m) = engine.run_depth(3);
@@ -0,0 +1,456 @@
+//! Plays UAI engines against each other.
+//!
+//! Usage: match --engine <command> [--name <name>] [--option <name>=<value>]...
+//!              --engine <command> ... [--tc <seconds>[+<increment>]] [--rounds N]
+//!              [--openings <file>] [--pgn <file>]
+//!              [--sprt elo0=<elo>,elo1=<elo>[,alpha=<p>][,beta=<p>]]
+//!
+//! Every pair of engines plays each opening twice, once with each colour, and does so `--rounds`
+//! times. The flags after an `--engine` configure that engine. The openings file has one FEN or
+//! EPD per line, and without one every game starts from the start position.
+//!
+//! With `--sprt`, two engines play until the test decides whether the first is better than the
+//! second, or until `--rounds` runs out if it's given.
+
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use snpataxx::game::{result_to_str, GameRecord, RecordedMove};
+use snpataxx::openings;
+use snpataxx::rules::{Color, GameResult, State};
+use snpataxx::stats::{Pentanomial, Sprt, SprtResult, Trinomial};
+use snpataxx::uai::client::{ClientError, EngineProcess};
+use snpataxx::uai::{GoParams, Score};
+
+/// Games that run this long are adjudicated by counting stones, since stones can jump forever.
+const MAX_GAME_PLIES: usize = 400;
+
+/// How late past its clock an engine may answer, to allow for pipes and scheduling.
+const TIME_MARGIN_MS: i64 = 100;
+
+const USAGE: &str = "Usage: match --engine <command> [--name <name>] [--option <name>=<value>]... \
+                     --engine <command> ... [--tc <seconds>[+<increment>]] [--rounds N] \
+                     [--openings <file>] [--pgn <file>] \
+                     [--sprt elo0=<elo>,elo1=<elo>[,alpha=<p>][,beta=<p>]]";
+
+struct EngineConfig {
+  /// The executable, followed by its arguments.
+  command: Vec<String>,
+  name:    Option<String>,
+  options: Vec<(String, String)>,
+}
+
+struct Config {
+  engines:  Vec<EngineConfig>,
+  base_ms:  i64,
+  inc_ms:   i64,
+  /// None plays until the SPRT decides, and without one means a single round.
+  rounds:   Option<usize>,
+  openings: Option<String>,
+  pgn:      Option<String>,
+  sprt:     Option<Sprt>,
+}
+
+fn parse_time_control(tc: &str) -> Result<(i64, i64), String> {
+  let (base, inc) = tc.split_once('+').unwrap_or((tc, "0"));
+  let seconds = |s: &str| match s.parse::<f64>() {
+    Ok(seconds) if seconds >= 0.0 => Ok((seconds * 1000.0).round() as i64),
+    _ => Err(format!("Invalid time control: {}", tc)),
+  };
+  Ok((seconds(base)?, seconds(inc)?))
+}
+
+fn parse_sprt(s: &str) -> Result<Sprt, String> {
+  let mut sprt = Sprt::default();
+  for parameter in s.split(',') {
+    let invalid = || format!("Invalid SPRT parameter: {}", parameter);
+    let (name, value) = parameter.split_once('=').ok_or_else(invalid)?;
+    let value = value.parse::<f64>().map_err(|_| invalid())?;
+    match name {
+      "elo0" => sprt.elo0 = value,
+      "elo1" => sprt.elo1 = value,
+      "alpha" => sprt.alpha = value,
+      "beta" => sprt.beta = value,
+      _ => return Err(invalid()),
+    }
+  }
+  if sprt.elo0 >= sprt.elo1 {
+    return Err("The SPRT needs elo0 below elo1".to_string());
+  }
+  Ok(sprt)
+}
+
+fn parse_args() -> Result<Config, String> {
+  let mut config = Config {
+    engines:  Vec::new(),
+    base_ms:  10_000,
+    inc_ms:   100,
+    rounds:   None,
+    openings: None,
+    pgn:      None,
+    sprt:     None,
+  };
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+    let engine = config.engines.last_mut();
+    match (arg.as_str(), engine) {
+      ("--engine", _) => config.engines.push(EngineConfig {
+        command: value.split_whitespace().map(|s| s.to_string()).collect(),
+        name:    None,
+        options: Vec::new(),
+      }),
+      ("--name", Some(engine)) => engine.name = Some(value),
+      ("--option", Some(engine)) => {
+        let (name, value) =
+          value.split_once('=').ok_or_else(|| format!("Expected <name>=<value>: {}", value))?;
+        engine.options.push((name.to_string(), value.to_string()));
+      }
+      ("--name" | "--option", None) => return Err(format!("{} must follow an --engine", arg)),
+      ("--tc", _) => (config.base_ms, config.inc_ms) = parse_time_control(&value)?,
+      ("--rounds", _) => {
+        config.rounds = Some(value.parse().map_err(|_| format!("Invalid rounds: {}", value))?)
+      }
+      ("--openings", _) => config.openings = Some(value),
+      ("--pgn", _) => config.pgn = Some(value),
+      ("--sprt", _) => config.sprt = Some(parse_sprt(&value)?),
+      _ => return Err(format!("Unknown flag: {}", arg)),
+    }
+  }
+  if config.engines.len() < 2 {
+    return Err("At least two engines are needed".to_string());
+  }
+  if config.sprt.is_some() && config.engines.len() != 2 {
+    return Err("An SPRT needs exactly two engines".to_string());
+  }
+  if config.engines.iter().any(|engine| engine.command.is_empty()) {
+    return Err("Empty engine command".to_string());
+  }
+  Ok(config)
+}
+
+/// Today's date in UTC, in PGN's `YYYY.MM.DD` form.
+fn pgn_date() -> String {
+  let days = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86400).unwrap_or(0);
+  // Howard Hinnant's days to civil date conversion, with years starting in March.
+  let z = days as i64 + 719468;
+  let era = z.div_euclid(146097);
+  let day_of_era = z.rem_euclid(146097);
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let shifted_month = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+  let month = match shifted_month < 10 {
+    true => shifted_month + 3,
+    false => shifted_month - 9,
+  };
+  let year = year_of_era + era * 400 + (month <= 2) as i64;
+  format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Starts an engine with its options set.
+fn start_engine(config: &EngineConfig) -> Result<EngineProcess, ClientError> {
+  let mut engine = EngineProcess::start(&config.command[0], &config.command[1..])?;
+  for (name, value) in &config.options {
+    engine.set_option(name, value)?;
+  }
+  engine.is_ready()?;
+  Ok(engine)
+}
+
+fn color_index(color: Color) -> usize {
+  match color {
+    Color::Black => 0,
+    Color::White => 1,
+  }
+}
+
+fn win_for(color: Color) -> GameResult {
+  match color {
+    Color::Black => GameResult::BlackWins,
+    Color::White => GameResult::WhiteWins,
+  }
+}
+
+/// Plays one game, with `players` as black and white. Returns the result and, for games that
+/// didn't end on the board, why they ended.
+fn play_game(
+  players: [&EngineConfig; 2],
+  opening: &State,
+  config: &Config,
+  record: &mut GameRecord,
+) -> (GameResult, Option<&'static str>) {
+  let mut engines = Vec::new();
+  for (color, player) in [Color::Black, Color::White].into_iter().zip(players) {
+    match start_engine(player).and_then(|mut engine| engine.new_game().map(|()| engine)) {
+      Ok(engine) => engines.push(engine),
+      Err(e) => {
+        eprintln!("{}: {}", player.command[0], e);
+        return (win_for(color.other_player()), Some("abandoned"));
+      }
+    }
+  }
+  let mut state = opening.clone();
+  let mut clocks = [config.base_ms; 2];
+  loop {
+    if let Some(result) = state.result() {
+      return (result, None);
+    }
+    if record.moves.len() >= MAX_GAME_PLIES {
+      return (state.result_by_material(), Some("adjudication"));
+    }
+    let mover = state.to_move;
+    let loss = win_for(mover.other_player());
+    let engine = &mut engines[color_index(mover)];
+    let moves = record.moves.iter().map(|recorded| recorded.m).collect::<Vec<_>>();
+    if engine.set_position(opening, &moves).is_err() {
+      return (loss, Some("abandoned"));
+    }
+    let params = GoParams {
+      btime: Some(clocks[0] as i32),
+      wtime: Some(clocks[1] as i32),
+      binc: Some(config.inc_ms as i32),
+      winc: Some(config.inc_ms as i32),
+      ..GoParams::default()
+    };
+
+    let start = Instant::now();
+    let allowed = Duration::from_millis((clocks[color_index(mover)] + TIME_MARGIN_MS) as u64);
+    let search = match engine.go(&params, allowed) {
+      Ok(search) => search,
+      Err(ClientError::Timeout) => return (loss, Some("time forfeit")),
+      Err(ClientError::Protocol(e)) => {
+        eprintln!("Bad reply from {}: {}", record_name(record, mover), e);
+        return (loss, Some("illegal move"));
+      }
+      Err(_) => return (loss, Some("abandoned")),
+    };
+    let clock = &mut clocks[color_index(mover)];
+    *clock -= start.elapsed().as_millis() as i64;
+    if *clock < -TIME_MARGIN_MS {
+      return (loss, Some("time forfeit"));
+    }
+    *clock += config.inc_ms;
+
+    let m = search.best_move;
+    if !state.is_legal(m) {
+      eprintln!(
+        "Illegal move from {}: {}",
+        record_name(record, mover),
+        m.to_uai()
+      );
+      return (loss, Some("illegal move"));
+    }
+    // Mate scores don't fit in the record's centistones.
+    let info = search.last_scored_info();
+    let score = match info.and_then(|info| info.score) {
+      Some(Score::Cp(score)) => Some(score),
+      _ => None,
+    };
+    state.make_move(m).unwrap();
+    record.moves.push(RecordedMove {
+      m,
+      score,
+      depth: info.and_then(|info| info.depth),
+    });
+  }
+}
+
+fn record_name(record: &GameRecord, color: Color) -> &str {
+  match color {
+    Color::Black => &record.black,
+    Color::White => &record.white,
+  }
+}
+
+fn print_table(names: &[String], standings: &[Trinomial]) {
+  let width = names.iter().map(|name| name.len()).max().unwrap_or(0).max(4);
+  println!(
+    "{:>4} {:<width$} {:>6} {:>6} {:>6} {:>6} {:>7}",
+    "Rank", "Name", "Games", "Wins", "Losses", "Draws", "Score"
+  );
+  let mut order = (0..names.len()).collect::<Vec<_>>();
+  order.sort_by(|a, b| standings[*b].score().total_cmp(&standings[*a].score()));
+  for (rank, i) in (1..).zip(order) {
+    let standing = &standings[i];
+    println!(
+      "{:>4} {:<width$} {:>6} {:>6} {:>6} {:>6} {:>6.1}%",
+      rank,
+      names[i],
+      standing.games(),
+      standing.wins,
+      standing.losses,
+      standing.draws,
+      100.0 * standing.score()
+    );
+  }
+}
+
+fn main() {
+  let config = parse_args().unwrap_or_else(|e| {
+    eprintln!("{}\n{}", e, USAGE);
+    std::process::exit(1);
+  });
+  let openings = match &config.openings {
+    Some(path) => openings::load(path).unwrap_or_else(|e| {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    }),
+    None => vec![State::startpos()],
+  };
+  let mut pgn = config.pgn.as_ref().map(|path| {
+    std::fs::OpenOptions::new().create(true).append(true).open(path).unwrap_or_else(|e| {
+      eprintln!("{}: {}", path, e);
+      std::process::exit(1);
+    })
+  });
+
+  // Start each engine once up front, to fail early and to learn its name.
+  let mut names = Vec::new();
+  for engine in &config.engines {
+    let name = match start_engine(engine) {
+      Ok(process) => engine.name.clone().or(process.name.clone()),
+      Err(e) => {
+        eprintln!("{}: {}", engine.command[0], e);
+        std::process::exit(1);
+      }
+    };
+    let mut name = name.unwrap_or_else(|| engine.command[0].clone());
+    // Two copies of an engine need telling apart.
+    if names.contains(&name) {
+      name = format!("{} ({})", name, names.len() + 1);
+    }
+    names.push(name);
+  }
+
+  let rounds = match config.sprt {
+    Some(_) => config.rounds,
+    None => Some(config.rounds.unwrap_or(1)),
+  };
+  // One round, in which every pair of engines plays each opening once with each colour.
+  let mut round_pairings = Vec::new();
+  for (opening_index, opening) in openings.iter().enumerate() {
+    for a in 0..names.len() {
+      for b in a + 1..names.len() {
+        round_pairings.push((opening_index, opening, a, b));
+        round_pairings.push((opening_index, opening, b, a));
+      }
+    }
+  }
+  let total_games = rounds.map(|rounds| rounds * round_pairings.len());
+  // The rounds are made as they're needed, since an SPRT can go on for any number of them.
+  let pairings =
+    (0..).take_while(|round| rounds.is_none_or(|rounds| *round < rounds)).flat_map(|round| {
+      round_pairings.iter().map(move |&(opening_index, opening, black, white)| {
+        (round, opening_index, opening, black, white)
+      })
+    });
+
+  let date = pgn_date();
+  let time_control = match config.inc_ms {
+    0 => format!("{}", config.base_ms as f64 / 1000.0),
+    inc => format!("{}+{}", config.base_ms as f64 / 1000.0, inc as f64 / 1000.0),
+  };
+  let mut standings = names.iter().map(|_| Trinomial::default()).collect::<Vec<_>>();
+  // With two engines, the first engine's results over each pair of games, which play one opening
+  // with each colour.
+  let mut pentanomial = Pentanomial::default();
+  let mut first_of_pair = None;
+  for (game, (round, opening_index, opening, black, white)) in (1..).zip(pairings) {
+    let mut record = GameRecord {
+      event: "snpataxx match".to_string(),
+      date: date.clone(),
+      black: names[black].clone(),
+      white: names[white].clone(),
+      fen: (*opening != State::startpos()).then(|| opening.to_fen()),
+      time_control: Some(time_control.clone()),
+      other_tags: vec![(
+        "Round".to_string(),
+        format!("{}.{}", round + 1, opening_index + 1),
+      )],
+      ..GameRecord::default()
+    };
+    let players = [&config.engines[black], &config.engines[white]];
+    let (result, termination) = play_game(players, opening, &config, &mut record);
+    record.result = Some(result);
+    if let Some(termination) = termination {
+      record.other_tags.push(("Termination".to_string(), termination.to_string()));
+    }
+    if let Some(pgn) = &mut pgn {
+      if let Err(e) = writeln!(pgn, "{}", record.to_pgn()) {
+        eprintln!("Couldn't write the PGN: {}", e);
+      }
+    }
+
+    match result {
+      GameResult::BlackWins => {
+        standings[black].wins += 1;
+        standings[white].losses += 1;
+      }
+      GameResult::WhiteWins => {
+        standings[white].wins += 1;
+        standings[black].losses += 1;
+      }
+      GameResult::Draw => {
+        standings[black].draws += 1;
+        standings[white].draws += 1;
+      }
+    }
+    println!(
+      "Game {}{}: {} vs {}: {}{}",
+      game,
+      total_games.map(|total| format!("/{}", total)).unwrap_or_default(),
+      record.black,
+      record.white,
+      result_to_str(Some(result)),
+      termination.map(|t| format!(" ({})", t)).unwrap_or_default()
+    );
+
+    if names.len() != 2 {
+      continue;
+    }
+    let first_engine_score = match (result, black == 0) {
+      (GameResult::Draw, _) => 0.5,
+      (GameResult::BlackWins, true) | (GameResult::WhiteWins, false) => 1.0,
+      _ => 0.0,
+    };
+    match first_of_pair.take() {
+      None => first_of_pair = Some(first_engine_score),
+      Some(first) => pentanomial.add_pair(first, first_engine_score),
+    }
+    if let (Some(sprt), None) = (&config.sprt, first_of_pair) {
+      let llr = pentanomial.llr(sprt);
+      let (lower, upper) = sprt.bounds();
+      println!(
+        "SPRT: llr {:.2}, lbound {:.2}, ubound {:.2}",
+        llr, lower, upper
+      );
+      match sprt.result(llr) {
+        SprtResult::Continue => {}
+        SprtResult::AcceptH0 => {
+          println!("H0 accepted: {} is not better than {}", names[0], names[1]);
+          break;
+        }
+        SprtResult::AcceptH1 => {
+          println!("H1 accepted: {} is better than {}", names[0], names[1]);
+          break;
+        }
+      }
+    }
+  }
+  println!();
+  print_table(&names, &standings);
+  if names.len() == 2 {
+    let elo = match pentanomial.pairs() {
+      0 => standings[0].elo(),
+      _ => pentanomial.elo(),
+    };
+    println!(
+      "\nElo difference: {:.1} +/- {:.1}, pentanomial {:?}",
+      elo.elo,
+      elo.error(),
+      pentanomial.counts
+    );
+  }
+}
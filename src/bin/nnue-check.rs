@@ -0,0 +1,68 @@
+//! Checks that incrementally updated NNUE accumulators match a full refresh.
+//!
+//! Usage: nnue-check [weights file] [--games N] [--seed N]
+//!
+//! Without a weights file a random network is used, which exercises the same code paths.
+
+use snpataxx::nnue::{Accumulator, Network};
+use snpataxx::rng::Rng;
+use snpataxx::rules::State;
+
+fn main() {
+  let args = std::env::args().skip(1).collect::<Vec<_>>();
+  let mut path = None;
+  let mut games = 100;
+  let mut seed = 1;
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--games" => games = args.next().and_then(|s| s.parse().ok()).expect("--games N"),
+      "--seed" => seed = args.next().and_then(|s| s.parse().ok()).expect("--seed N"),
+      _ => path = Some(arg.clone()),
+    }
+  }
+  let rng = Rng::new(seed);
+  let network = match path {
+    Some(path) => Network::load(&path).unwrap_or_else(|e| panic!("{}", e)),
+    None => Network::random(64, &rng),
+  };
+  println!(
+    "Checking a network with hidden size {}",
+    network.hidden_size()
+  );
+
+  let mut positions = 0;
+  let mut moves = Vec::new();
+  for game in 0..games {
+    let mut state = State::startpos();
+    let mut accumulator = Accumulator::refresh(&network, &state);
+    for ply in 0..300 {
+      if state.game_is_over() {
+        break;
+      }
+      moves.clear();
+      state.move_gen(&mut moves);
+      let m = moves[rng.generate_range(moves.len() as u32) as usize];
+      let delta = state.make_move_delta(m).unwrap();
+      accumulator.update(&network, &delta);
+      let refreshed = Accumulator::refresh(&network, &state);
+      if accumulator != refreshed {
+        eprintln!(
+          "Mismatch in game {} at ply {} after {}",
+          game,
+          ply,
+          m.to_uai()
+        );
+        eprintln!("{}", state.render());
+        std::process::exit(1);
+      }
+      let (incremental, full) = (
+        network.evaluate(&accumulator, state.to_move),
+        network.evaluate(&refreshed, state.to_move),
+      );
+      assert_eq!(incremental, full);
+      positions += 1;
+    }
+  }
+  println!("OK: {} positions in {} games matched", positions, games);
+}
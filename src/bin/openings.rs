@@ -0,0 +1,85 @@
+//! Generates opening positions with symmetric gap patterns.
+//!
+//! Usage: openings <output file> [--count N] [--symmetry horizontal,vertical,rotational]
+//!                 [--min-gaps N] [--max-gaps N] [--random-plies N] [--depth N] [--max-score N]
+//!                 [--seed N]
+//!
+//! The openings are written one FEN per line, which `match --openings` and `datagen --openings`
+//! read. Openings a search to `--depth` scores beyond `--max-score` centistones are left out.
+
+use snpataxx::openings::{self, OpeningConfig, Symmetry};
+
+const USAGE: &str = "Usage: openings <output file> [--count N] \
+                     [--symmetry horizontal,vertical,rotational] [--min-gaps N] [--max-gaps N] \
+                     [--random-plies N] [--depth N] [--max-score N] [--seed N]";
+
+struct Config {
+  output:  String,
+  count:   usize,
+  seed:    u64,
+  opening: OpeningConfig,
+}
+
+fn parse_args() -> Result<Config, String> {
+  let mut config = Config {
+    output:  String::new(),
+    count:   100,
+    seed:    rand::random(),
+    opening: OpeningConfig::default(),
+  };
+  let args = std::env::args().skip(1).collect::<Vec<_>>();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let mut value = || {
+      let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+      value.parse::<u64>().map_err(|_| format!("Invalid number: {}", value))
+    };
+    match arg.as_str() {
+      "--count" => config.count = value()? as usize,
+      "--min-gaps" => config.opening.min_gaps = value()? as u32,
+      "--max-gaps" => config.opening.max_gaps = value()? as u32,
+      "--random-plies" => config.opening.random_plies = value()? as usize,
+      "--depth" => config.opening.depth = value()? as u16,
+      "--max-score" => config.opening.max_score = value()? as i32,
+      "--seed" => config.seed = value()?,
+      "--symmetry" => {
+        let names = args.next().ok_or("Missing value for --symmetry")?;
+        config.opening.symmetries =
+          names.split(',').map(Symmetry::parse).collect::<Result<_, _>>()?;
+      }
+      arg if arg.starts_with("--") => return Err(format!("Unknown flag: {}", arg)),
+      arg => config.output = arg.to_string(),
+    }
+  }
+  if config.output.is_empty() {
+    return Err("Missing output file".to_string());
+  }
+  if config.opening.min_gaps > config.opening.max_gaps {
+    return Err("--min-gaps is more than --max-gaps".to_string());
+  }
+  Ok(config)
+}
+
+fn main() {
+  let config = parse_args().unwrap_or_else(|e| {
+    eprintln!("{}", e);
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+  });
+  println!(
+    "Generating {} openings with seed {}",
+    config.count, config.seed
+  );
+  let openings = openings::generate(&config.opening, config.count, config.seed);
+  if openings.len() < config.count {
+    eprintln!(
+      "Only found {} fair openings, try a higher --max-score",
+      openings.len()
+    );
+  }
+  if let Err(e) = openings::save(&config.output, &openings) {
+    eprintln!("{}", e);
+    std::process::exit(1);
+  }
+  println!("Wrote {} openings to {}", openings.len(), config.output);
+}
@@ -1,28 +1,91 @@
+use snpataxx::perft::{divide, perft, PerftCache};
 use snpataxx::rules::State;
 
-fn perft(depth: usize, state: State) -> usize {
-  if depth == 0 {
-    return 1;
-  }
-  let mut moves = Vec::new();
-  state.move_gen(&mut moves);
-  if depth == 1 {
-    return moves.len();
+const USAGE: &str =
+  "Usage: perft <depth> [fen] [--divide] [--hash <MB>] [--threads <N>]\nThe FEN defaults to the start position.";
+
+struct Args {
+  depth:   u16,
+  fen:     Option<String>,
+  divide:  bool,
+  hash_mb: usize,
+  threads: usize,
+}
+
+fn parse_args() -> Result<Args, String> {
+  let mut depth = None;
+  let mut fen_parts = Vec::new();
+  let mut args = Args {
+    depth:   0,
+    fen:     None,
+    divide:  false,
+    hash_mb: 0,
+    threads: 1,
+  };
+  let mut iter = std::env::args().skip(1);
+  while let Some(arg) = iter.next() {
+    let mut value = |name: &str| {
+      iter
+        .next()
+        .and_then(|v| v.parse::<usize>().ok())
+        .ok_or_else(|| format!("Missing or invalid value for {}", name))
+    };
+    match arg.as_str() {
+      "--divide" => args.divide = true,
+      "--hash" => args.hash_mb = value("--hash")?,
+      "--threads" => args.threads = value("--threads")?,
+      _ if depth.is_none() => {
+        depth = Some(arg.parse::<u16>().map_err(|_| format!("Invalid depth: {}", arg))?)
+      }
+      _ => fen_parts.push(arg),
+    }
   }
-  let mut total = 0;
-  for m in moves {
-    let mut new_state = state.clone();
-    new_state.make_move(m).unwrap();
-    new_state.sanity_check();
-    total += perft(depth - 1, new_state);
+  args.depth = depth.ok_or("Missing depth")?;
+  if !fen_parts.is_empty() {
+    args.fen = Some(fen_parts.join(" "));
   }
-  total
+  Ok(args)
 }
 
 fn main() {
-  let state = snpataxx::rules::State::from_fen("x5o/7/7/7/7/7/o5x x 0 1").unwrap();
-  println!("{}", perft(6, state));
-  //for i in 0..6 {
-  //  println!("{} {}", i, perft(i, state.clone()));
-  //}
+  let args = match parse_args() {
+    Ok(args) => args,
+    Err(e) => {
+      eprintln!("{}\n{}", e, USAGE);
+      std::process::exit(1);
+    }
+  };
+  let state = match &args.fen {
+    Some(fen) => State::from_fen(fen).unwrap_or_else(|e| {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    }),
+    None => State::startpos(),
+  };
+  let cache = match args.hash_mb {
+    0 => None,
+    size_mb => Some(PerftCache::new(size_mb)),
+  };
+
+  let start = std::time::Instant::now();
+  let total = match (args.divide, args.threads) {
+    (false, 1) => perft(&state, args.depth, cache.as_ref()),
+    _ => {
+      let counts = divide(&state, args.depth, args.threads, cache.as_ref());
+      if args.divide {
+        for (m, count) in &counts {
+          println!("{} {}", m.to_uai(), count);
+        }
+        println!();
+      }
+      counts.iter().map(|(_, count)| count).sum()
+    }
+  };
+  let elapsed = start.elapsed().as_secs_f64();
+  println!("nodes {}", total);
+  println!(
+    "time {:.3} s, {:.0} nodes/s",
+    elapsed,
+    total as f64 / elapsed.max(1e-9)
+  );
 }
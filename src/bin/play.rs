@@ -0,0 +1,245 @@
+//! Play against the engine in the terminal.
+//!
+//! Usage: play [--time <ms>] [--seed N]
+//!
+//! Moves are entered in UAI notation, like `b6` or `a7c5`. Type `help` during the game for the
+//! other commands.
+
+use std::io::{BufRead, Write};
+
+use snpataxx::game::{GameRecord, RecordedMove};
+use snpataxx::rules::{Color, GameResult, Move, State};
+use snpataxx::search::Engine;
+
+const DEFAULT_MOVETIME_MS: i32 = 1000;
+
+/// Games that run this long are adjudicated by counting stones, since stones can jump forever.
+const MAX_GAME_PLIES: usize = 400;
+
+const HELP: &str = "Commands:
+  <move>           play a move, like b6 or a7c5
+  undo, takeback   take back your last move and the engine's reply
+  hint             ask the engine for a move
+  time <ms>        set how long the engine thinks per move
+  save <file>      save the game as PGN
+  help             show this message
+  quit             leave the game";
+
+struct Game {
+  /// The starting position's FEN, or None for the start position.
+  start_fen: Option<String>,
+  start:     State,
+  /// The engine's moves keep its score and depth.
+  moves:     Vec<RecordedMove>,
+  state:     State,
+  human:     Color,
+}
+
+impl Game {
+  fn replay(&mut self) {
+    self.state = self.start.clone();
+    for recorded in &self.moves {
+      self.state.make_move(recorded.m).unwrap();
+    }
+  }
+
+  fn play(&mut self, recorded: RecordedMove) {
+    self.state.make_move(recorded.m).unwrap();
+    self.moves.push(recorded);
+  }
+
+  fn result(&self) -> Option<GameResult> {
+    match self.moves.len() >= MAX_GAME_PLIES {
+      true => Some(self.state.result_by_material()),
+      false => self.state.result(),
+    }
+  }
+
+  fn human_must_pass(&self) -> bool {
+    self.state.to_move == self.human && self.result().is_none() && self.state.is_legal(Move::PASS)
+  }
+
+  /// Takes back moves until it's the human's turn with a real choice again. Returns false if there
+  /// was nothing to take back.
+  fn undo(&mut self) -> bool {
+    if self.moves.is_empty() {
+      return false;
+    }
+    loop {
+      self.moves.pop();
+      self.replay();
+      if self.moves.is_empty() || (self.state.to_move == self.human && !self.human_must_pass()) {
+        return true;
+      }
+    }
+  }
+
+  fn to_record(&self) -> GameRecord {
+    let (black, white) = match self.human {
+      Color::Black => ("Human", "snpataxx"),
+      Color::White => ("snpataxx", "Human"),
+    };
+    GameRecord {
+      event: "Human vs engine".to_string(),
+      black: black.to_string(),
+      white: white.to_string(),
+      fen: self.start_fen.clone(),
+      moves: self.moves.clone(),
+      result: self.result(),
+      ..GameRecord::default()
+    }
+  }
+}
+
+fn parse_args() -> Result<(i32, u64), String> {
+  let mut movetime_ms = DEFAULT_MOVETIME_MS;
+  let mut seed = rand::random();
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+    match arg.as_str() {
+      "--time" => movetime_ms = value.parse().map_err(|_| format!("Invalid time: {}", value))?,
+      "--seed" => seed = value.parse().map_err(|_| format!("Invalid seed: {}", value))?,
+      _ => return Err(format!("Unknown flag: {}", arg)),
+    }
+  }
+  Ok((movetime_ms, seed))
+}
+
+/// Prompts until the reader gives a line, or returns None at the end of input.
+fn prompt(lines: &mut impl Iterator<Item = String>, text: &str) -> Option<String> {
+  print!("{}", text);
+  std::io::stdout().flush().unwrap();
+  lines.next().map(|line| line.trim().to_string())
+}
+
+fn describe_search(engine: &Engine, m: Move) -> String {
+  let pv = match engine.lines().first() {
+    Some(line) => line.pv.iter().map(|m| m.to_uai()).collect::<Vec<_>>().join(" "),
+    None => m.to_uai(),
+  };
+  let score = engine.lines().first().map(|line| line.score).unwrap_or(0);
+  format!("{} [eval: {}, pv: {}]", m.to_uai(), score, pv)
+}
+
+fn describe_result(result: GameResult, human: Color) -> &'static str {
+  match (result, human) {
+    (GameResult::Draw, _) => "The game is drawn.",
+    (GameResult::BlackWins, Color::Black) | (GameResult::WhiteWins, Color::White) => "You win!",
+    _ => "The engine wins.",
+  }
+}
+
+fn main() {
+  let (mut movetime_ms, seed) = parse_args().unwrap_or_else(|e| {
+    eprintln!("{}", e);
+    eprintln!("Usage: play [--time <ms>] [--seed N]");
+    std::process::exit(1);
+  });
+  println!("seed {}", seed);
+  let mut engine = Engine::new(seed);
+  let mut lines = std::io::stdin().lock().lines().map_while(Result::ok);
+
+  let human = loop {
+    match prompt(&mut lines, "Play as x or o [x]: ").as_deref() {
+      None => return,
+      Some("" | "x" | "X") => break Color::Black,
+      Some("o" | "O") => break Color::White,
+      Some(other) => println!("Unknown colour: {}", other),
+    }
+  };
+  let (start_fen, start) = loop {
+    match prompt(&mut lines, "FEN [start position]: ") {
+      None => return,
+      Some(fen) if fen.is_empty() => break (None, State::startpos()),
+      Some(fen) => match State::from_fen(&fen) {
+        Ok(state) => break (Some(fen), state),
+        Err(e) => println!("{}", e),
+      },
+    }
+  };
+  let mut game = Game {
+    start_fen,
+    state: start.clone(),
+    start,
+    moves: Vec::new(),
+    human,
+  };
+  println!("{}", HELP);
+
+  // Only redraw the board when it changed.
+  let mut moved = true;
+  loop {
+    if moved {
+      println!("\n{}", game.state.render());
+      if let Some(result) = game.result() {
+        println!("{}", describe_result(result, human));
+      }
+      moved = false;
+    }
+    let game_over = game.result().is_some();
+    if !game_over && game.state.to_move != human {
+      engine.set_position(game.state.clone());
+      let (_, m) = engine.run_time(movetime_ms);
+      let m = m.unwrap();
+      println!("Engine plays {}", describe_search(&engine, m));
+      let line = engine.lines().first();
+      game.play(RecordedMove {
+        m,
+        score: line.map(|line| line.score),
+        depth: line.map(|line| line.depth),
+      });
+      moved = true;
+      continue;
+    }
+    if game.human_must_pass() {
+      println!("You have no moves, so you pass.");
+      game.play(RecordedMove::new(Move::PASS));
+      moved = true;
+      continue;
+    }
+
+    let Some(line) = prompt(&mut lines, "> ") else {
+      return;
+    };
+    let tokens = line.split_whitespace().collect::<Vec<_>>();
+    match tokens.as_slice() {
+      [] => {}
+      ["help"] => println!("{}", HELP),
+      ["quit"] => return,
+      ["undo" | "takeback"] => {
+        moved = game.undo();
+        if !moved {
+          println!("There is nothing to take back.");
+        }
+      }
+      ["hint"] if !game_over => {
+        engine.set_position(game.state.clone());
+        if let (_, Some(m)) = engine.run_time(movetime_ms) {
+          println!("Hint: {}", describe_search(&engine, m));
+        }
+      }
+      ["time", ms] => match ms.parse::<i32>() {
+        Ok(ms) if ms > 0 => {
+          movetime_ms = ms;
+          println!("The engine now thinks for {} ms per move.", ms);
+        }
+        _ => println!("Invalid time: {}", ms),
+      },
+      ["save", path] => match std::fs::write(path, game.to_record().to_pgn()) {
+        Ok(()) => println!("Saved to {}", path),
+        Err(e) => println!("Couldn't save to {}: {}", path, e),
+      },
+      [m] if !game_over => match Move::from_uai(m) {
+        Ok(m) if game.state.is_legal(m) => {
+          game.play(RecordedMove::new(m));
+          moved = true;
+        }
+        Ok(_) => println!("Illegal move: {}", m),
+        Err(e) => println!("{}", e),
+      },
+      [_] if game_over => println!("The game is over. You can still undo, save or quit."),
+      _ => println!("Unknown command: {}. Type help for the commands.", line),
+    }
+  }
+}
@@ -0,0 +1,224 @@
+//! Measures the Elo of each skill level, for `skill::SKILL_ELO`.
+//!
+//! Usage: skill-calibrate [--games N] [--threads N] [--full-nodes N] [--seed N]
+//!
+//! Each level plays `--games` games against the level below it, and level 0 plays `RandomMover`,
+//! which is rated 0. Games come in pairs that play the same random opening once with each colour.
+//! Levels below full strength are bounded by their own node limits, and full strength searches
+//! `--full-nodes` nodes per move. Each level's Elo difference is printed with its 95% confidence
+//! interval as soon as its games are done. At the end the differences are chained into a table in
+//! the form `SKILL_ELO` takes, unless a level won or lost every game, which has no finite Elo.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use snpataxx::baselines::RandomMover;
+use snpataxx::rng::Rng;
+use snpataxx::rules::{Color, GameResult, State};
+use snpataxx::search::{Engine, SearchLimits, Searcher};
+use snpataxx::skill::MAX_SKILL_LEVEL;
+
+/// Games that run this long are adjudicated by counting stones, since stones can jump forever.
+const MAX_GAME_PLIES: usize = 400;
+
+/// Random plies at the start of each pair, so the games differ.
+const OPENING_PLIES: usize = 4;
+
+/// Wins, losses and draws of a level against the level below.
+#[derive(Clone, Copy, Default)]
+struct Results {
+  wins:   u32,
+  losses: u32,
+  draws:  u32,
+}
+
+impl Results {
+  fn games(&self) -> u32 {
+    self.wins + self.losses + self.draws
+  }
+
+  fn score(&self) -> f64 {
+    (self.wins as f64 + 0.5 * self.draws as f64) / self.games().max(1) as f64
+  }
+
+  /// The Elo difference and its 95% confidence interval. Bounds past a perfect score are
+  /// infinite.
+  fn elo(&self) -> (f64, f64, f64) {
+    let (games, score) = (self.games().max(1) as f64, self.score());
+    let variance = [(1.0, self.wins), (0.0, self.losses), (0.5, self.draws)]
+      .iter()
+      .map(|(points, count)| *count as f64 * (points - score).powi(2))
+      .sum::<f64>()
+      / games;
+    let margin = 1.96 * (variance / games).sqrt();
+    (
+      score_to_elo(score),
+      score_to_elo((score - margin).max(0.0)),
+      score_to_elo((score + margin).min(1.0)),
+    )
+  }
+}
+
+/// The Elo difference at which the stronger player expects `score`.
+fn score_to_elo(score: f64) -> f64 {
+  -400.0 * (1.0 / score - 1.0).log10()
+}
+
+const USAGE: &str = "Usage: skill-calibrate [--games N] [--threads N] [--full-nodes N] [--seed N]";
+
+struct Config {
+  games:      usize,
+  threads:    usize,
+  full_nodes: u64,
+  seed:       u64,
+}
+
+fn parse_args() -> Result<Config, String> {
+  let args = std::env::args().skip(1).collect::<Vec<_>>();
+  let mut config = Config {
+    games:      200,
+    threads:    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+    full_nodes: 1_000_000,
+    seed:       1,
+  };
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let mut value = || {
+      let value = args.next().ok_or_else(|| format!("Missing value for {}", arg))?;
+      value.parse::<u64>().map_err(|_| format!("Invalid number: {}", value))
+    };
+    match arg.as_str() {
+      // Games come in pairs.
+      "--games" => config.games = (value()? as usize).div_ceil(2) * 2,
+      "--threads" => config.threads = value()?.max(1) as usize,
+      "--full-nodes" => config.full_nodes = value()?,
+      "--seed" => config.seed = value()?,
+      arg => return Err(format!("Unknown argument: {}", arg)),
+    }
+  }
+  Ok(config)
+}
+
+/// A searcher at the given level, where level -1 is `RandomMover`.
+fn player(level: i32, seed: u64) -> Box<dyn Searcher> {
+  if level < 0 {
+    return Box::new(RandomMover::new(seed));
+  }
+  let mut engine = Engine::new(seed);
+  engine.skill_level = level as u8;
+  Box::new(engine)
+}
+
+/// Plays one game, and returns the result for `level`, which plays the first colour of the pair
+/// in even games and the second in odd ones.
+fn play_game(config: &Config, level: u8, game: usize) -> f64 {
+  let pair_seed =
+    config.seed.wrapping_mul(1000).wrapping_add((level as usize * 10_000 + game / 2) as u64);
+  let rng = Rng::new(pair_seed);
+  let mut state = State::startpos();
+  let mut moves = Vec::new();
+  for _ in 0..OPENING_PLIES {
+    moves.clear();
+    state.move_gen(&mut moves);
+    state.make_move(moves[rng.generate_range(moves.len() as u32) as usize]).unwrap();
+  }
+  let game_seed = pair_seed.wrapping_mul(2).wrapping_add(game as u64 % 2);
+  let mut tested = player(level as i32, game_seed);
+  let mut opponent = player(level as i32 - 1, game_seed.wrapping_add(1));
+  let tested_color = match game % 2 {
+    0 => Color::Black,
+    _ => Color::White,
+  };
+  tested.set_position(state.clone());
+  opponent.set_position(state.clone());
+  let limits = SearchLimits {
+    // Lower levels stop at their own node limit before this.
+    nodes: Some(config.full_nodes),
+    ..SearchLimits::default()
+  };
+  for _ in 0..MAX_GAME_PLIES {
+    if state.game_is_over() {
+      break;
+    }
+    let mover = match state.to_move == tested_color {
+      true => &mut tested,
+      false => &mut opponent,
+    };
+    let m = mover.search(&limits).1.unwrap();
+    state.make_move(m).unwrap();
+    tested.make_move(m).unwrap();
+    opponent.make_move(m).unwrap();
+  }
+  match (state.result_by_material(), tested_color) {
+    (GameResult::Draw, _) => 0.5,
+    (GameResult::BlackWins, Color::Black) | (GameResult::WhiteWins, Color::White) => 1.0,
+    _ => 0.0,
+  }
+}
+
+fn main() {
+  let config = parse_args().unwrap_or_else(|e| {
+    eprintln!("{}", e);
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+  });
+  let levels = MAX_SKILL_LEVEL as usize + 1;
+  let results = Mutex::new(vec![Results::default(); levels]);
+  let next_game = AtomicUsize::new(0);
+  std::thread::scope(|scope| {
+    for _ in 0..config.threads {
+      let (config, results, next_game) = (&config, &results, &next_game);
+      scope.spawn(move || loop {
+        let index = next_game.fetch_add(1, Ordering::Relaxed);
+        if index >= levels * config.games {
+          break;
+        }
+        let (level, game) = (index / config.games, index % config.games);
+        let score = play_game(config, level as u8, game);
+        let mut results = results.lock().unwrap();
+        let result = &mut results[level];
+        if score == 1.0 {
+          result.wins += 1;
+        } else if score == 0.0 {
+          result.losses += 1;
+        } else {
+          result.draws += 1;
+        }
+        if result.games() as usize == config.games {
+          let (step, lower, upper) = result.elo();
+          println!(
+            "Level {:>2} vs {:>6}: +{} -{} ={}, step {:.0} ({:.0} to {:.0})",
+            level,
+            match level {
+              0 => "random".to_string(),
+              _ => format!("{}", level - 1),
+            },
+            result.wins,
+            result.losses,
+            result.draws,
+            step,
+            lower,
+            upper
+          );
+        }
+      });
+    }
+  });
+
+  let results = results.into_inner().unwrap();
+  if let Some(level) = results.iter().position(|result| result.elo().0.is_infinite()) {
+    eprintln!("Level {} won or lost every game, so try more games", level);
+    std::process::exit(1);
+  }
+  let table = results
+    .iter()
+    .scan(0.0, |elo, result| {
+      *elo += result.elo().0;
+      Some(elo.round() as i32)
+    })
+    .collect::<Vec<_>>();
+  println!(
+    "pub const SKILL_ELO: [i32; MAX_SKILL_LEVEL as usize + 1] = {:?};",
+    table
+  );
+}
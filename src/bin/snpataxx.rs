@@ -1,118 +1,522 @@
-use std::{collections::HashMap, io::BufRead};
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 use snpataxx::{
-  rules::{Color, State},
-  search::Engine,
+  baselines::{GreedyMover, OnePlyMinimax, RandomMover},
+  bench,
+  book::Book,
+  mcts::Mcts,
+  options::{OptionValue, OptionsRegistry, UaiOption},
+  perft,
+  rng::Rng,
+  rules::{Color, Move, State},
+  search::{
+    Engine, EvalParams, Evaluation, IterationInfo, SearchLimits, SearchObserver, SearchStats,
+    Searcher, StopHandle, DEFAULT_HASH_MB,
+  },
+  skill,
+  uai::{parse_command, CommandError, EngineMessage, GoParams, GuiCommand, Info, Score},
 };
 
+fn declare_options(engine: &Engine, others: &[Box<dyn Searcher>]) -> Vec<UaiOption> {
+  let mut search_names = vec![engine.name()];
+  search_names.extend(others.iter().map(|s| s.name()));
+  let mut options = vec![
+    UaiOption::spin("Hash", DEFAULT_HASH_MB as i64, 1, 65536),
+    UaiOption::spin("Threads", engine.threads as i64, 1, 256),
+    UaiOption::spin("MultiPV", engine.multi_pv as i64, 1, 256),
+    UaiOption::check("Ponder", false),
+    UaiOption::spin("Move Overhead", engine.move_overhead_ms as i64, 0, 5000),
+    UaiOption::spin("Random Noise", engine.noise as i64, 0, 1000),
+    // Zero keeps the random seed picked at startup.
+    UaiOption::spin("Seed", 0, 0, i32::MAX as i64),
+    UaiOption::spin(
+      "Skill Level",
+      engine.skill_level as i64,
+      0,
+      skill::MAX_SKILL_LEVEL as i64,
+    ),
+    UaiOption::check("UAI_LimitStrength", false),
+    UaiOption::spin(
+      "UAI_Elo",
+      skill::SKILL_ELO[skill::MAX_SKILL_LEVEL as usize] as i64,
+      skill::SKILL_ELO[0] as i64,
+      skill::SKILL_ELO[skill::MAX_SKILL_LEVEL as usize] as i64,
+    ),
+    UaiOption::string("Debug Log File", ""),
+    UaiOption::string("EvalFile", ""),
+    UaiOption::check("OwnBook", false),
+    UaiOption::string("BookFile", ""),
+    UaiOption::combo("Search", engine.name(), &search_names),
+  ];
+  if cfg!(feature = "nnue") {
+    options.push(UaiOption::string("NnueFile", ""));
+  }
+  options
+}
+
+/// Writes protocol lines to stdout, and mirrors the whole conversation into the debug log file if
+/// one is set. Clones share the log, so searchers can report through their observers.
+#[derive(Clone, Default)]
+struct Output {
+  log: Arc<Mutex<Option<std::fs::File>>>,
+}
+
+impl Output {
+  fn send(&self, line: &str) {
+    println!("{}", line);
+    self.log_line(&format!(">> {}", line));
+  }
+
+  fn send_message(&self, message: EngineMessage) {
+    self.send(&message.to_string());
+  }
+
+  fn received(&self, line: &str) {
+    self.log_line(&format!("<< {}", line));
+  }
+
+  fn log_line(&self, line: &str) {
+    if let Some(log) = self.log.lock().unwrap().as_mut() {
+      let _ = writeln!(log, "{}", line);
+    }
+  }
+}
+
+/// Turns search progress into info lines. Best move changes only go to the log, because the
+/// iteration's info lines already carry them.
+impl SearchObserver for Output {
+  fn iteration_completed(&mut self, info: &IterationInfo) {
+    for (i, line) in info.lines.iter().enumerate() {
+      self.send_message(EngineMessage::Info(Info {
+        depth: Some(info.depth),
+        multipv: Some(i as u32 + 1),
+        score: Some(Score::Cp(line.score)),
+        wdl: line.wdl,
+        nodes: Some(info.nodes),
+        time_ms: Some(info.time_ms),
+        pv: line.pv.clone(),
+        ..Info::default()
+      }));
+    }
+  }
+
+  fn best_move_changed(&mut self, depth: u16, score: Evaluation, m: Move) {
+    self.log_line(&format!(
+      "-- best move {} at depth {} score {}",
+      m.to_uai(),
+      depth,
+      score
+    ));
+  }
+
+  fn search_finished(&mut self, stats: &SearchStats) {
+    self.send_message(EngineMessage::Info(Info {
+      depth: Some(stats.depth),
+      nodes: Some(stats.nodes),
+      time_ms: Some(stats.time_ms),
+      nps: Some(stats.nodes_per_second()),
+      ..Info::default()
+    }));
+  }
+
+  fn debug(&mut self, message: &str) {
+    self.send(&format!("info string {}", message));
+  }
+}
+
+/// The opening book from BookFile, which go plays from while OwnBook is on.
+struct OwnBook {
+  book: Option<Book>,
+  rng:  Rng,
+}
+
+/// Applies a new option value to the engine as soon as it is set.
+fn apply_option(
+  name: &str,
+  value: &OptionValue,
+  engine: &mut Engine,
+  others: &mut [Box<dyn Searcher>],
+  book: &mut OwnBook,
+  options: &OptionsRegistry,
+  output: &Output,
+) -> Result<(), String> {
+  // The registry already checked that the value has the option's type.
+  let spin = || value.as_spin().unwrap_or_default();
+  let string = || value.as_str().unwrap_or_default();
+  match name {
+    "Hash" => engine.set_hash_size_mb(spin() as usize),
+    "Threads" => engine.threads = spin() as usize,
+    "MultiPV" => engine.multi_pv = spin() as usize,
+    "Move Overhead" => engine.move_overhead_ms = spin() as i32,
+    "Random Noise" => engine.noise = spin() as u32,
+    "Seed" if spin() != 0 => {
+      engine.set_seed(spin() as u64);
+      book.rng = Rng::new(engine.seed());
+      output.send(&format!("info string seed {}", engine.seed()));
+    }
+    "Skill Level" | "UAI_LimitStrength" | "UAI_Elo" => {
+      // A limited Elo overrides the skill level.
+      let option = |name| options.get(name).cloned().unwrap_or(OptionValue::Button);
+      engine.skill_level = match option("UAI_LimitStrength").as_check() {
+        Some(true) => skill::skill_for_elo(option("UAI_Elo").as_spin().unwrap_or_default() as i32),
+        _ => option("Skill Level").as_spin().unwrap_or_default() as u8,
+      };
+    }
+    "Debug Log File" => {
+      *output.log.lock().unwrap() = match string() {
+        "" => None,
+        path => Some(std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?),
+      }
+    }
+    "EvalFile" => {
+      engine.eval_params = match string() {
+        "" => EvalParams::default(),
+        path => EvalParams::load(path)?,
+      }
+    }
+    "BookFile" => {
+      book.book = match string() {
+        "" => None,
+        path => Some(Book::load(path)?),
+      }
+    }
+    "Search" => {
+      // Make sure the new searcher starts from the current position.
+      if let Some(other) = others.iter_mut().find(|s| s.name() == string()) {
+        other.set_position(engine.state.clone());
+      }
+    }
+    #[cfg(feature = "nnue")]
+    "NnueFile" => {
+      let network = match string() {
+        "" => None,
+        path => Some(std::sync::Arc::new(snpataxx::nnue::Network::load(path)?)),
+      };
+      engine.set_network(network);
+    }
+    // Ponder only changes what the GUI sends us.
+    _ => {}
+  }
+  Ok(())
+}
+
+/// Prints the move count below each legal move, and the total.
+fn go_perft(state: &State, depth: u16, output: &Output) {
+  let counts = perft::divide(state, depth, 1, None);
+  for (m, count) in &counts {
+    output.send(&format!("info string {} {}", m.to_uai(), count));
+  }
+  let total = counts.iter().map(|(_, count)| count).sum::<u64>();
+  output.send(&format!("info string nodes {}", total));
+}
+
+/// Searches the bench positions, and prints the node count and speed in the form testing
+/// frameworks look for.
+fn bench(depth: Option<u16>, output: &Output) {
+  let result = bench::run_bench(depth.unwrap_or(bench::DEFAULT_BENCH_DEPTH));
+  output.send(&format!(
+    "{} nodes {} nps",
+    result.nodes,
+    result.nodes_per_second()
+  ));
+}
+
+/// The searchers, which a go hands to the search thread until its best move is out.
+struct Searchers {
+  engine: Engine,
+  /// The alpha-beta engine always tracks the position, and these are handed it on each go.
+  others: Vec<Box<dyn Searcher>>,
+}
+
+impl Searchers {
+  /// The stop handle of the searcher the Search option picks, which is the engine unless another
+  /// has that name.
+  fn stop_handle(&self, name: &str) -> StopHandle {
+    match self.others.iter().find(|s| s.name() == name) {
+      Some(other) => other.stop_handle(),
+      None => self.engine.stop_handle(),
+    }
+  }
+}
+
+/// Runs searches on their own thread, so the input loop can still hear stop.
+struct SearchThread {
+  idle:    Option<Searchers>,
+  running: Option<(StopHandle, JoinHandle<Searchers>)>,
+}
+
+impl SearchThread {
+  /// The searchers, once the search in progress, if any, has sent its best move.
+  fn searchers(&mut self) -> &mut Searchers {
+    if let Some((_, thread)) = self.running.take() {
+      self.idle = Some(thread.join().unwrap());
+    }
+    self.idle.as_mut().unwrap()
+  }
+
+  fn start(&mut self, stop: StopHandle, search: impl FnOnce(&mut Searchers) + Send + 'static) {
+    let mut searchers = self.idle.take().unwrap();
+    let thread = std::thread::spawn(move || {
+      search(&mut searchers);
+      searchers
+    });
+    self.running = Some((stop, thread));
+  }
+
+  /// Stops the search in progress, and waits for its best move.
+  fn stop(&mut self) {
+    if let Some((stop, thread)) = &self.running {
+      // A search that hasn't started yet clears the stop, so keep asking until it's done.
+      while !thread.is_finished() {
+        stop.stop();
+        std::thread::sleep(Duration::from_millis(1));
+      }
+    }
+    self.searchers();
+  }
+}
+
+/// Starts searching the engine's position with whichever searcher is selected. The search thread
+/// plays the best move when it's done.
+fn go(
+  command: &GoParams,
+  search: &mut SearchThread,
+  book: &OwnBook,
+  options: &OptionsRegistry,
+  output: &Output,
+) {
+  let searchers = search.searchers();
+  if let Some(depth) = command.perft {
+    return go_perft(&searchers.engine.state, depth, output);
+  }
+  let book_move = match options.get("OwnBook").and_then(|v| v.as_check()) {
+    Some(true) => book.book.as_ref().and_then(|b| b.pick(&searchers.engine.state, &book.rng)),
+    _ => None,
+  };
+  if let Some(m) = book_move {
+    output.send(&format!("info string book move {}", m.to_uai()));
+    output.send_message(EngineMessage::BestMove { m, ponder: None });
+    searchers.engine.make_move(m).unwrap();
+    return;
+  }
+  let mut limits = SearchLimits {
+    depth:       command.depth,
+    nodes:       command.nodes,
+    movetime_ms: command.movetime,
+    clock:       None,
+    moves_to_go: command.movestogo,
+  };
+  // Without any other limit, use time controls. Without those either, search until stopped.
+  if limits.depth.is_none() && limits.nodes.is_none() && limits.movetime_ms.is_none() {
+    limits.clock = match searchers.engine.state.to_move {
+      Color::White => command.wtime.map(|time| (time, command.winc.unwrap_or(0))),
+      Color::Black => command.btime.map(|time| (time, command.binc.unwrap_or(0))),
+    };
+  }
+  // The GUI decides when an infinite or ponder search is over, even if we finish first.
+  let hold = command.infinite || command.ponder;
+  let search_name = options.get("Search").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+  let stop = searchers.stop_handle(&search_name);
+  let output = output.clone();
+  search.start(stop.clone(), move |searchers| {
+    search_and_play(
+      searchers,
+      &search_name,
+      &limits,
+      hold.then_some(&stop),
+      &output,
+    )
+  });
+}
+
+/// Searches on the search thread and plays the best move. With `hold_until`, the best move waits
+/// until that handle is stopped.
+fn search_and_play(
+  searchers: &mut Searchers,
+  search_name: &str,
+  limits: &SearchLimits,
+  hold_until: Option<&StopHandle>,
+  output: &Output,
+) {
+  let Searchers { engine, others } = searchers;
+  let searcher: &mut dyn Searcher = match others.iter_mut().find(|s| s.name() == search_name) {
+    Some(other) => {
+      other.set_position(engine.state.clone());
+      other.as_mut()
+    }
+    None => engine,
+  };
+  let reports_progress = searcher.reports_progress();
+  let (score, m) = searcher.search(limits);
+  if let Some(stop) = hold_until {
+    while !stop.is_stopped() {
+      std::thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  // Make sure we only pass when we have no moves!
+  if m == Some(Move::PASS) && !engine.state.is_legal(Move::PASS) {
+    output.send("info string Searcher passed with legal moves available");
+  }
+  match m {
+    Some(m) => {
+      // Searchers without an observer still need to tell the GUI their score.
+      if !reports_progress {
+        output.send(&format!("info score cp {} pv {}", score, m.to_uai()));
+      }
+      output.send_message(EngineMessage::BestMove { m, ponder: None });
+      if let Err(e) = engine.make_move(m) {
+        output.send(&format!("info string {}", e));
+      }
+    }
+    // The game is over, so there's nothing to play.
+    None => output.send_message(EngineMessage::BestMove {
+      m:      Move::PASS,
+      ponder: None,
+    }),
+  }
+}
+
 fn main() {
   let stdin = std::io::stdin();
-  let mut options = HashMap::new();
+  let output = Output::default();
   let mut engine = Engine::new(rand::random());
-  engine.set_position(State::from_fen("x5o/7/7/7/7/7/o5x x 0 1").unwrap());
-
-  for line in stdin.lock().lines().map(|r| r.unwrap()) {
-    let tokens = line.split_whitespace().collect::<Vec<_>>();
-    if tokens.is_empty() {
-      continue;
-    }
-    match tokens[0] {
-      "uai" => {
-        println!("id name snpataxx");
-        println!("id author Peter Schmidt-Nielsen");
-        println!("uaiok");
+  engine.move_overhead_ms = 10;
+  engine.set_observer(Some(Box::new(output.clone())));
+  let mut mcts = Mcts::new(rand::random());
+  mcts.move_overhead_ms = engine.move_overhead_ms;
+  mcts.set_observer(Some(Box::new(output.clone())));
+  engine.set_position(State::startpos());
+  // Which searcher searches is picked with --search or setoption Search.
+  let others: Vec<Box<dyn Searcher>> = vec![
+    Box::new(mcts),
+    Box::new(RandomMover::new(rand::random())),
+    Box::new(GreedyMover::new(rand::random())),
+    Box::new(OnePlyMinimax::new(rand::random())),
+  ];
+  let mut options = OptionsRegistry::new(declare_options(&engine, &others));
+  let mut book = OwnBook {
+    book: None,
+    rng:  Rng::new(engine.seed()),
+  };
+  let mut search = SearchThread {
+    idle:    Some(Searchers { engine, others }),
+    running: None,
+  };
+
+  let args = std::env::args().collect::<Vec<_>>();
+  if args.get(1).map(|a| a.as_str()) == Some("bench") {
+    match args.get(2).map(|d| d.parse()) {
+      None => bench(None, &output),
+      Some(Ok(depth)) => bench(Some(depth), &output),
+      Some(Err(_)) => {
+        eprintln!("Invalid bench depth: {}", args[2]);
+        std::process::exit(1);
+      }
+    }
+    return;
+  }
+
+  // Options can also be given on the command line, as --eval-file and --search.
+  for (flag, name) in [("--eval-file", "EvalFile"), ("--search", "Search")] {
+    if let Some(i) = args.iter().position(|a| a == flag) {
+      let searchers = search.searchers();
+      let result = match args.get(i + 1) {
+        Some(value) => options.set(name, value).and_then(|(name, value)| {
+          apply_option(
+            &name,
+            &value,
+            &mut searchers.engine,
+            &mut searchers.others,
+            &mut book,
+            &options,
+            &output,
+          )
+        }),
+        None => Err(format!("Missing value for {}", flag)),
+      };
+      if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
       }
-      "uaiok" => {}
-      "uainewgame" => engine = Engine::new(rand::random()),
-      "isready" => println!("readyok"),
-      "quit" => break,
-      "setoption" => {
-        assert_eq!(tokens[1], "name");
-        assert_eq!(tokens[3], "value");
-        let name = tokens[2];
-        let value = tokens[4];
-        options.insert(name.to_string(), value.to_string());
+    }
+  }
+
+  for line in stdin.lock().lines() {
+    let line = match line {
+      Ok(line) => line,
+      Err(e) => {
+        output.send(&format!("info string Unreadable input: {}", e));
+        continue;
       }
-      "dbg" => {
-        engine.state.render();
+    };
+    output.received(&line);
+    let command = match parse_command(&line) {
+      Ok(command) => command,
+      Err(CommandError::Unknown) => continue,
+      Err(CommandError::Malformed(e)) => {
+        output.send(&format!("info string {}", e));
+        continue;
       }
-      "position" => match tokens[1] {
-        "startpos" => {
-          engine = Engine::new(rand::random());
-          if tokens.len() > 2 {
-            assert_eq!(tokens[2], "moves");
-            let moves = &tokens[3..];
-            for m in moves {
-              let m = snpataxx::rules::Move::from_uai(m);
-              engine.make_move(m).unwrap();
-            }
-          }
-        }
-        "fen" => {
-          let fen_string = tokens[2..tokens.len() - 1].join(" ");
-          let state = snpataxx::rules::State::from_fen(&fen_string).unwrap();
-          engine.set_position(state);
-        }
-        _ => panic!("Unknown position command: {}", line),
-      },
-      "go" => {
-        let mut depth = None;
-        let mut btime = 10;
-        let mut wtime = 10;
-        let mut binc = 1000;
-        let mut winc = 1000;
-        let mut movetime = None;
-        for i in 1..tokens.len() {
-          match tokens[i] {
-            "depth" => depth = Some(tokens[i + 1].parse().unwrap()),
-            "btime" => btime = tokens[i + 1].parse().unwrap(),
-            "wtime" => wtime = tokens[i + 1].parse().unwrap(),
-            "binc" => binc = tokens[i + 1].parse().unwrap(),
-            "winc" => winc = tokens[i + 1].parse().unwrap(),
-            "movetime" => movetime = Some(tokens[i + 1].parse().unwrap()),
-            _ => (),
-          }
+    };
+    match command {
+      GuiCommand::Uai => {
+        output.send_message(EngineMessage::IdName("snpataxx".to_string()));
+        output.send_message(EngineMessage::IdAuthor("Peter Schmidt-Nielsen".to_string()));
+        for option in options.declarations() {
+          output.send_message(EngineMessage::Option(option.clone()));
         }
-
-        let (score, m) = match (depth, movetime) {
-          (Some(_), Some(_)) => panic!("Cannot specify both depth and movetime"),
-          // If we have a specified depth, use that.
-          (Some(depth), _) => engine.run_depth(depth),
-          // If we have a specified time, use that.
-          (_, Some(movetime)) => engine.run_time(movetime),
-          // Otherwise, use time controls.
-          (None, None) => {
-            let (time, inc) = match engine.state.to_move {
-              Color::White => (wtime, winc),
-              Color::Black => (btime, binc),
-            };
-            engine.run_time_managed(time, inc)
-          }
-        };
-
-        if m == Some(snpataxx::rules::Move::PASS) {
-          // Make sure we have no moves!
-          let mut moves = vec![];
-          engine.state.move_gen(&mut moves);
-          if moves.len() > 0 {
-            panic!(
-              "PASS move when we have other moves: {:?}\n{}",
-              moves,
-              engine.state.render()
-            );
-          }
+        output.send(&format!(
+          "info string seed {}",
+          search.searchers().engine.seed()
+        ));
+        output.send_message(EngineMessage::UaiOk);
+      }
+      GuiCommand::UaiNewGame => {
+        let engine = &mut search.searchers().engine;
+        engine.clear();
+        engine.set_position(State::startpos());
+      }
+      GuiCommand::IsReady => output.send_message(EngineMessage::ReadyOk),
+      GuiCommand::SetOption { name, value } => {
+        let searchers = search.searchers();
+        let result =
+          options.set(&name, value.as_deref().unwrap_or_default()).and_then(|(name, value)| {
+            apply_option(
+              &name,
+              &value,
+              &mut searchers.engine,
+              &mut searchers.others,
+              &mut book,
+              &options,
+              &output,
+            )
+          });
+        if let Err(e) = result {
+          output.send(&format!("info string {}", e));
         }
-        match m {
-          Some(m) => {
-            println!("bestmove {}", m.to_uai());
-            println!("info score cp {} pv {}", score, m.to_uai());
-            engine.make_move(m).unwrap();
-          }
-          None => println!("bestmove xyzw"),
+      }
+      GuiCommand::Debug => {
+        for line in search.searchers().engine.state.render().lines() {
+          output.send(&format!("info string {}", line));
         }
       }
-      "stop" => {}
-      _ => panic!("Unknown command: {}", line),
+      // Only uainewgame resets the engine, so the move ordering table survives across the
+      // positions of one game.
+      GuiCommand::Position(position) => match position.state() {
+        Ok(state) => search.searchers().engine.set_position(state),
+        Err(e) => output.send(&format!("info string {}", e)),
+      },
+      GuiCommand::Go(command) => go(&command, &mut search, &book, &options, &output),
+      GuiCommand::Bench(depth) => bench(depth, &output),
+      // We don't think on the opponent's time, so a ponderhit just asks for the move.
+      GuiCommand::Stop | GuiCommand::PonderHit => search.stop(),
+      GuiCommand::Quit => break,
     }
   }
+  // Don't leave a search running without its best move.
+  search.stop();
 }
@@ -0,0 +1,170 @@
+//! Texel-style tuner for the evaluation weights.
+//!
+//! Usage: tune <positions file> <output weights file> [initial weights file]
+//!
+//! Each line of the positions file is a FEN followed by a `|` and the game outcome from black's
+//! point of view, either as `1-0`, `0-1`, `1/2-1/2` or a number between 0 and 1. Files ending in
+//! `.bin` are instead read as the packed positions written by `datagen`.
+
+use snpataxx::data::PackedPosition;
+use snpataxx::rules::{GameResult, State};
+use snpataxx::search::{eval_features, EvalParams, EVAL_FEATURE_COUNT, EVAL_FEATURE_NAMES};
+
+const EPOCHS: usize = 2000;
+const LEARNING_RATE: f64 = 2000.0;
+
+struct Sample {
+  features: [f64; EVAL_FEATURE_COUNT],
+  outcome:  f64,
+}
+
+fn parse_outcome(s: &str) -> Result<f64, String> {
+  match s {
+    "1-0" => Ok(1.0),
+    "0-1" => Ok(0.0),
+    "1/2-1/2" => Ok(0.5),
+    _ => match s.parse::<f64>() {
+      Ok(x) if (0.0..=1.0).contains(&x) => Ok(x),
+      _ => Err(format!("Invalid outcome: {}", s)),
+    },
+  }
+}
+
+fn load_packed_samples(path: &str) -> Result<Vec<Sample>, String> {
+  let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+  let mut reader = std::io::BufReader::new(file);
+  let mut samples = Vec::new();
+  while let Some(position) =
+    PackedPosition::read_from(&mut reader).map_err(|e| format!("{}: {}", path, e))?
+  {
+    if position.state.game_is_over() {
+      continue;
+    }
+    samples.push(Sample {
+      features: eval_features(&position.state).map(|f| f as f64),
+      outcome:  match position.result {
+        GameResult::BlackWins => 1.0,
+        GameResult::WhiteWins => 0.0,
+        GameResult::Draw => 0.5,
+      },
+    });
+  }
+  Ok(samples)
+}
+
+fn load_samples(path: &str) -> Result<Vec<Sample>, String> {
+  if path.ends_with(".bin") {
+    return load_packed_samples(path);
+  }
+  let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+  let mut samples = Vec::new();
+  for (line_number, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() {
+      continue;
+    }
+    let context = |e: String| format!("{}:{}: {}", path, line_number + 1, e);
+    let (fen, outcome) = line.rsplit_once('|').ok_or_else(|| context("Missing |".to_string()))?;
+    let state = State::from_fen(fen.trim()).map_err(context)?;
+    // Terminal positions are scored by the game result, not the weights.
+    if state.game_is_over() {
+      continue;
+    }
+    samples.push(Sample {
+      features: eval_features(&state).map(|f| f as f64),
+      outcome:  parse_outcome(outcome.trim()).map_err(context)?,
+    });
+  }
+  Ok(samples)
+}
+
+fn sigmoid(k: f64, eval: f64) -> f64 {
+  1.0 / (1.0 + (-k * eval / 400.0).exp())
+}
+
+fn linear_eval(weights: &[f64], features: &[f64]) -> f64 {
+  weights.iter().zip(features).map(|(w, f)| w * f).sum()
+}
+
+fn mean_loss(k: f64, weights: &[f64], samples: &[Sample]) -> f64 {
+  let total: f64 = samples
+    .iter()
+    .map(|s| (s.outcome - sigmoid(k, linear_eval(weights, &s.features))).powi(2))
+    .sum();
+  total / samples.len() as f64
+}
+
+/// Finds the scaling constant that best fits the initial weights, by ternary search.
+fn fit_k(weights: &[f64], samples: &[Sample]) -> f64 {
+  let (mut lo, mut hi) = (0.0, 10.0);
+  for _ in 0..100 {
+    let a = lo + (hi - lo) / 3.0;
+    let b = hi - (hi - lo) / 3.0;
+    match mean_loss(a, weights, samples) < mean_loss(b, weights, samples) {
+      true => hi = b,
+      false => lo = a,
+    }
+  }
+  (lo + hi) / 2.0
+}
+
+fn main() {
+  let args = std::env::args().collect::<Vec<_>>();
+  if args.len() < 3 || args.len() > 4 {
+    eprintln!(
+      "Usage: {} <positions file> <output weights file> [initial weights file]",
+      args[0]
+    );
+    std::process::exit(1);
+  }
+  let initial = match args.get(3) {
+    Some(path) => EvalParams::load(path).unwrap_or_else(|e| panic!("{}", e)),
+    None => EvalParams::default(),
+  };
+  let samples = load_samples(&args[1]).unwrap_or_else(|e| panic!("{}", e));
+  if samples.is_empty() {
+    panic!("No positions to tune on");
+  }
+  println!("Loaded {} positions", samples.len());
+
+  let mut weights = initial.weights.map(|w| w as f64);
+  let k = fit_k(&weights, &samples);
+  println!(
+    "K = {:.4}, initial loss = {:.6}",
+    k,
+    mean_loss(k, &weights, &samples)
+  );
+
+  // Plain gradient descent on the mean squared error of the win probability.
+  for epoch in 0..EPOCHS {
+    let mut gradient = [0.0; EVAL_FEATURE_COUNT];
+    for sample in &samples {
+      let p = sigmoid(k, linear_eval(&weights, &sample.features));
+      // d/dw (outcome - p)^2 = -2 (outcome - p) p (1 - p) k / 400 * features
+      let scale = -2.0 * (sample.outcome - p) * p * (1.0 - p) * k / 400.0;
+      for (g, f) in gradient.iter_mut().zip(sample.features) {
+        *g += scale * f;
+      }
+    }
+    for (w, g) in weights.iter_mut().zip(gradient) {
+      *w -= LEARNING_RATE * g / samples.len() as f64;
+    }
+    if epoch % 100 == 0 {
+      println!(
+        "epoch {} loss = {:.6}",
+        epoch,
+        mean_loss(k, &weights, &samples)
+      );
+    }
+  }
+
+  let tuned = EvalParams {
+    weights: weights.map(|w| w.round() as i32),
+  };
+  let tuned_loss = mean_loss(k, &tuned.weights.map(|w| w as f64), &samples);
+  println!("Final loss = {:.6}", tuned_loss);
+  for (name, weight) in EVAL_FEATURE_NAMES.iter().zip(tuned.weights) {
+    println!("  {} = {}", name, weight);
+  }
+  std::fs::write(&args[2], tuned.to_file_string()).unwrap_or_else(|e| panic!("{}: {}", args[2], e));
+}
@@ -0,0 +1,199 @@
+//! Opening books: moves to play in known positions, looked up by `State::hash_with_gaps`.
+//!
+//! On disk a book is a list of `BookEntry` records sorted by key, each the key as a
+//! little-endian u64, the packed move and the weight as little-endian u16s, then the wins, draws
+//! and losses as little-endian u32s.
+
+use std::collections::HashMap;
+
+use crate::game::GameRecord;
+use crate::rng::Rng;
+use crate::rules::{Color, GameResult, Move, State};
+
+/// Size of one record on disk.
+pub const BOOK_ENTRY_BYTES: usize = 24;
+
+/// One move in one position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+  /// The position's `State::hash_with_gaps`.
+  pub hash:   u64,
+  pub m:      Move,
+  /// How often to play the move, relative to the position's other moves. Zero never plays it.
+  pub weight: u16,
+  /// The results of the games the move was played in, for the side that played it.
+  pub wins:   u32,
+  pub draws:  u32,
+  pub losses: u32,
+}
+
+impl BookEntry {
+  pub fn to_bytes(&self) -> [u8; BOOK_ENTRY_BYTES] {
+    let mut bytes = [0; BOOK_ENTRY_BYTES];
+    bytes[0..8].copy_from_slice(&self.hash.to_le_bytes());
+    bytes[8..10].copy_from_slice(&self.m.pack().to_le_bytes());
+    bytes[10..12].copy_from_slice(&self.weight.to_le_bytes());
+    bytes[12..16].copy_from_slice(&self.wins.to_le_bytes());
+    bytes[16..20].copy_from_slice(&self.draws.to_le_bytes());
+    bytes[20..24].copy_from_slice(&self.losses.to_le_bytes());
+    bytes
+  }
+
+  pub fn from_bytes(bytes: &[u8; BOOK_ENTRY_BYTES]) -> BookEntry {
+    let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+    BookEntry {
+      hash:   u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+      m:      Move::unpack(u16_at(8)),
+      weight: u16_at(10),
+      wins:   u32_at(12),
+      draws:  u32_at(16),
+      losses: u32_at(20),
+    }
+  }
+
+  pub fn games(&self) -> u32 {
+    self.wins + self.draws + self.losses
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Book {
+  /// Sorted by key, so a position's moves are next to each other.
+  entries: Vec<BookEntry>,
+}
+
+impl Book {
+  pub fn new(mut entries: Vec<BookEntry>) -> Book {
+    // Sorting by move too keeps files reproducible, whatever order the entries come in.
+    entries.sort_by_key(|entry| (entry.hash, entry.m.pack()));
+    Book { entries }
+  }
+
+  pub fn entries(&self) -> &[BookEntry] {
+    &self.entries
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// The position's entries. Keys can collide, so moves that aren't legal here are left out.
+  pub fn moves(&self, state: &State) -> Vec<&BookEntry> {
+    let hash = state.hash_with_gaps();
+    let start = self.entries.partition_point(|entry| entry.hash < hash);
+    self.entries[start..]
+      .iter()
+      .take_while(|entry| entry.hash == hash)
+      .filter(|entry| state.is_legal(entry.m))
+      .collect()
+  }
+
+  /// Picks one of the position's moves at random, in proportion to their weights.
+  pub fn pick(&self, state: &State, rng: &Rng) -> Option<Move> {
+    let moves = self.moves(state);
+    let total = moves.iter().map(|entry| entry.weight as u32).sum::<u32>();
+    if total == 0 {
+      return None;
+    }
+    let mut choice = rng.generate_range(total);
+    for entry in moves {
+      if choice < entry.weight as u32 {
+        return Some(entry.m);
+      }
+      choice -= entry.weight as u32;
+    }
+    unreachable!()
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Book, String> {
+    if !bytes.len().is_multiple_of(BOOK_ENTRY_BYTES) {
+      return Err(format!(
+        "Book size {} isn't a multiple of {}",
+        bytes.len(),
+        BOOK_ENTRY_BYTES
+      ));
+    }
+    let entries = bytes
+      .chunks_exact(BOOK_ENTRY_BYTES)
+      .map(|chunk| BookEntry::from_bytes(chunk.try_into().unwrap()))
+      .collect();
+    Ok(Book::new(entries))
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    self.entries.iter().flat_map(|entry| entry.to_bytes()).collect()
+  }
+
+  pub fn load(path: &str) -> Result<Book, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    Book::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))
+  }
+
+  pub fn save(&self, path: &str) -> Result<(), String> {
+    std::fs::write(path, self.to_bytes()).map_err(|e| format!("{}: {}", path, e))
+  }
+}
+
+/// Counts the moves played in the openings of a collection of games.
+pub struct BookBuilder {
+  /// Only moves up to this ply are counted.
+  max_plies: usize,
+  counts:    HashMap<(u64, u16), BookEntry>,
+}
+
+impl BookBuilder {
+  pub fn new(max_plies: usize) -> BookBuilder {
+    BookBuilder {
+      max_plies,
+      counts: HashMap::new(),
+    }
+  }
+
+  /// Counts a game's opening moves, with the game's result. Returns false for games without a
+  /// result, which are skipped.
+  pub fn add_game(&mut self, game: &GameRecord) -> Result<bool, String> {
+    let Some(result) = game.result else {
+      return Ok(false);
+    };
+    let positions = game.replay()?;
+    for (state, recorded) in positions.iter().zip(&game.moves).take(self.max_plies) {
+      let hash = state.hash_with_gaps();
+      let entry = self.counts.entry((hash, recorded.m.pack())).or_insert(BookEntry {
+        hash,
+        m: recorded.m,
+        weight: 0,
+        wins: 0,
+        draws: 0,
+        losses: 0,
+      });
+      match (result, state.to_move) {
+        (GameResult::Draw, _) => entry.draws += 1,
+        (GameResult::BlackWins, Color::Black) | (GameResult::WhiteWins, Color::White) => {
+          entry.wins += 1
+        }
+        _ => entry.losses += 1,
+      }
+    }
+    Ok(true)
+  }
+
+  /// A book of the moves played in at least `min_games` games, weighted by the points they
+  /// scored: two for a win and one for a draw.
+  pub fn build(&self, min_games: u32) -> Book {
+    let entries = self
+      .counts
+      .values()
+      .filter(|entry| entry.games() >= min_games)
+      .map(|entry| BookEntry {
+        weight: (2 * entry.wins + entry.draws).min(u16::MAX as u32) as u16,
+        ..*entry
+      })
+      .collect();
+    Book::new(entries)
+  }
+}
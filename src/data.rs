@@ -0,0 +1,81 @@
+use std::io::{Read, Write};
+
+use crate::rules::{Color, GameResult, State};
+
+/// Size of one record on disk.
+pub const PACKED_POSITION_BYTES: usize = 28;
+
+/// A training position, as written by `datagen`.
+///
+/// On disk this is the black stones, white stones and gaps as little-endian u64s, then the
+/// search score as a little-endian i16, then the side to move, then the game result.
+#[derive(Clone)]
+pub struct PackedPosition {
+  pub state:  State,
+  /// Search score from the point of view of the side to move, clamped to the i16 range.
+  pub score:  i16,
+  /// The final result of the game this position came from.
+  pub result: GameResult,
+}
+
+impl PackedPosition {
+  pub fn to_bytes(&self) -> [u8; PACKED_POSITION_BYTES] {
+    let mut bytes = [0; PACKED_POSITION_BYTES];
+    bytes[0..8].copy_from_slice(&self.state.black_stones.to_le_bytes());
+    bytes[8..16].copy_from_slice(&self.state.white_stones.to_le_bytes());
+    bytes[16..24].copy_from_slice(&self.state.gaps.to_le_bytes());
+    bytes[24..26].copy_from_slice(&self.score.to_le_bytes());
+    bytes[26] = match self.state.to_move {
+      Color::Black => 0,
+      Color::White => 1,
+    };
+    bytes[27] = match self.result {
+      GameResult::BlackWins => 0,
+      GameResult::WhiteWins => 1,
+      GameResult::Draw => 2,
+    };
+    bytes
+  }
+
+  pub fn from_bytes(bytes: &[u8; PACKED_POSITION_BYTES]) -> Result<PackedPosition, String> {
+    let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+    let state = State {
+      black_stones: u64_at(0),
+      white_stones: u64_at(8),
+      gaps:         u64_at(16),
+      to_move:      match bytes[26] {
+        0 => Color::Black,
+        1 => Color::White,
+        b => return Err(format!("Invalid side to move: {}", b)),
+      },
+    };
+    let result = match bytes[27] {
+      0 => GameResult::BlackWins,
+      1 => GameResult::WhiteWins,
+      2 => GameResult::Draw,
+      b => return Err(format!("Invalid game result: {}", b)),
+    };
+    Ok(PackedPosition {
+      state,
+      score: i16::from_le_bytes([bytes[24], bytes[25]]),
+      result,
+    })
+  }
+
+  pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+    writer.write_all(&self.to_bytes())
+  }
+
+  /// Reads the next record, or returns None at the end of the file.
+  pub fn read_from(reader: &mut impl Read) -> std::io::Result<Option<PackedPosition>> {
+    let mut bytes = [0; PACKED_POSITION_BYTES];
+    match reader.read_exact(&mut bytes) {
+      Ok(()) => {}
+      Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+      Err(e) => return Err(e),
+    }
+    PackedPosition::from_bytes(&bytes)
+      .map(Some)
+      .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+  }
+}
@@ -0,0 +1,353 @@
+//! Game records, in the PGN dialect that cutechess-cli writes for Ataxx.
+//!
+//! Moves are in UAI notation, each optionally followed by a `{score/depth}` comment with the
+//! mover's score in stones. As in chess PGN, `1-0` means the player in the White tag won. White is
+//! `o`, and Black, `x`, moves first.
+
+use crate::rules::{Color, GameResult, Move, State};
+use crate::search::Evaluation;
+
+/// Score comments like `+M5` count as a win this many centistones up, less the plies to go.
+const WIN_SCORE: Evaluation = 1_000_000;
+
+/// Movetext lines are wrapped at this width.
+const LINE_WIDTH: usize = 79;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedMove {
+  pub m:     Move,
+  /// The mover's search score in centistones, if the move had a score comment.
+  pub score: Option<Evaluation>,
+  pub depth: Option<u16>,
+}
+
+impl RecordedMove {
+  pub fn new(m: Move) -> RecordedMove {
+    RecordedMove {
+      m,
+      score: None,
+      depth: None,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameRecord {
+  pub event:        String,
+  /// In PGN's `YYYY.MM.DD` form.
+  pub date:         String,
+  pub black:        String,
+  pub white:        String,
+  /// The starting position, or None for the start position.
+  pub fen:          Option<String>,
+  pub time_control: Option<String>,
+  /// Tags we don't interpret, like Site and Round, kept in order so records round-trip.
+  pub other_tags:   Vec<(String, String)>,
+  pub moves:        Vec<RecordedMove>,
+  /// None for a game that isn't finished, written as `*`.
+  pub result:       Option<GameResult>,
+}
+
+impl Default for GameRecord {
+  fn default() -> GameRecord {
+    GameRecord {
+      event:        "?".to_string(),
+      date:         "????.??.??".to_string(),
+      black:        "?".to_string(),
+      white:        "?".to_string(),
+      fen:          None,
+      time_control: None,
+      other_tags:   Vec::new(),
+      moves:        Vec::new(),
+      result:       None,
+    }
+  }
+}
+
+/// The result as PGN writes it, with `*` for an unfinished game.
+pub fn result_to_str(result: Option<GameResult>) -> &'static str {
+  match result {
+    Some(GameResult::WhiteWins) => "1-0",
+    Some(GameResult::BlackWins) => "0-1",
+    Some(GameResult::Draw) => "1/2-1/2",
+    None => "*",
+  }
+}
+
+fn result_from_str(s: &str) -> Option<Option<GameResult>> {
+  match s {
+    "1-0" => Some(Some(GameResult::WhiteWins)),
+    "0-1" => Some(Some(GameResult::BlackWins)),
+    "1/2-1/2" => Some(Some(GameResult::Draw)),
+    "*" => Some(None),
+    _ => None,
+  }
+}
+
+/// Parses a tag pair like `[Event "Test match"]`.
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+  let malformed = || format!("Malformed tag: {}", line);
+  let inner = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')).ok_or_else(malformed)?;
+  let (name, value) = inner.trim().split_once(char::is_whitespace).ok_or_else(malformed)?;
+  let value =
+    value.trim().strip_prefix('"').and_then(|v| v.strip_suffix('"')).ok_or_else(malformed)?;
+  let mut unescaped = String::new();
+  let mut chars = value.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => unescaped.push(chars.next().ok_or_else(malformed)?),
+      c => unescaped.push(c),
+    }
+  }
+  Ok((name.to_string(), unescaped))
+}
+
+/// Parses a score like `+0.25`, or a win like `-M4`, into centistones.
+fn parse_score(s: &str) -> Option<Evaluation> {
+  let (sign, magnitude) = match s.strip_prefix('-') {
+    Some(rest) => (-1, rest),
+    None => (1, s.strip_prefix('+').unwrap_or(s)),
+  };
+  let centistones = match magnitude.strip_prefix('M') {
+    Some(plies) => WIN_SCORE - plies.parse::<Evaluation>().ok()?,
+    None => {
+      let stones = magnitude.parse::<f64>().ok().filter(|s| s.is_finite())?;
+      (stones * 100.0).round() as Evaluation
+    }
+  };
+  Some(sign * centistones)
+}
+
+/// Reads the score and depth from the start of a comment like `{+0.25/9 0.52s}`. Other comments,
+/// like `{book}`, have neither.
+fn parse_score_comment(comment: &str) -> (Option<Evaluation>, Option<u16>) {
+  let first = comment.split_whitespace().next().unwrap_or("");
+  let (score, depth) = match first.split_once('/') {
+    Some((score, depth)) => (score, depth.parse().ok()),
+    None => (first, None),
+  };
+  match parse_score(score) {
+    Some(score) => (Some(score), depth),
+    None => (None, None),
+  }
+}
+
+impl GameRecord {
+  /// Parses a file of games, separated wherever a tag follows movetext.
+  pub fn parse_all(text: &str) -> Result<Vec<GameRecord>, String> {
+    let mut games = Vec::new();
+    let mut game = String::new();
+    let mut in_movetext = false;
+    for line in text.lines() {
+      let line = line.trim();
+      // Lines starting with % are escaped, for tools to use.
+      if line.starts_with('%') {
+        continue;
+      }
+      let is_tag = line.starts_with('[');
+      if is_tag && in_movetext {
+        games.push(GameRecord::parse(&game)?);
+        game.clear();
+        in_movetext = false;
+      }
+      in_movetext |= !is_tag && !line.is_empty();
+      game.push_str(line);
+      game.push('\n');
+    }
+    if !game.trim().is_empty() {
+      games.push(GameRecord::parse(&game)?);
+    }
+    Ok(games)
+  }
+
+  /// Parses a single game.
+  pub fn parse(text: &str) -> Result<GameRecord, String> {
+    let mut record = GameRecord::default();
+    let mut tag_result = None;
+    let mut movetext = String::new();
+    for line in text.lines().map(|line| line.trim()) {
+      if !line.starts_with('[') {
+        movetext.push_str(line);
+        movetext.push('\n');
+        continue;
+      }
+      let (name, value) = parse_tag(line)?;
+      match name.as_str() {
+        "Event" => record.event = value,
+        "Date" => record.date = value,
+        "Black" => record.black = value,
+        "White" => record.white = value,
+        "Result" => {
+          tag_result =
+            Some(result_from_str(&value).ok_or_else(|| format!("Invalid result: {}", value))?)
+        }
+        "FEN" => record.fen = Some(value),
+        "TimeControl" => record.time_control = Some(value),
+        _ => record.other_tags.push((name, value)),
+      }
+    }
+
+    let mut termination = None;
+    let mut chars = movetext.chars().peekable();
+    while let Some(&c) = chars.peek() {
+      if c.is_whitespace() {
+        chars.next();
+        continue;
+      }
+      if termination.is_some() {
+        return Err("Movetext continues after the result".to_string());
+      }
+      match c {
+        '{' => {
+          chars.next();
+          let comment = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+          // Comments before the first move, or after a move's score, are just for people.
+          if let Some(last) = record.moves.last_mut().filter(|last| last.score.is_none()) {
+            (last.score, last.depth) = parse_score_comment(&comment);
+          }
+        }
+        ';' => {
+          chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+        }
+        '(' => return Err("Variations aren't supported".to_string()),
+        _ => {
+          let mut token = String::new();
+          while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || "{;(".contains(c) {
+              break;
+            }
+            token.push(c);
+            chars.next();
+          }
+          if let Some(result) = result_from_str(&token) {
+            termination = Some(result);
+            continue;
+          }
+          // Numeric annotation glyphs.
+          if token.starts_with('$') {
+            continue;
+          }
+          // Move numbers, like `12.` or `12...`, which may run into the move.
+          let m = match token.starts_with(|c: char| c.is_ascii_digit()) && token.contains('.') {
+            true => token.trim_start_matches(|c: char| c.is_ascii_digit()).trim_start_matches('.'),
+            false => &token,
+          };
+          if !m.is_empty() {
+            record.moves.push(RecordedMove::new(Move::from_uai(m)?));
+          }
+        }
+      }
+    }
+
+    record.result = match (tag_result, termination) {
+      (Some(tag), Some(termination)) if tag != termination => {
+        return Err(format!(
+          "Result tag {} doesn't match the movetext's {}",
+          result_to_str(tag),
+          result_to_str(termination)
+        ))
+      }
+      (_, Some(result)) | (Some(result), None) => result,
+      (None, None) => None,
+    };
+    Ok(record)
+  }
+
+  pub fn start_state(&self) -> Result<State, String> {
+    match &self.fen {
+      Some(fen) => State::from_fen(fen),
+      None => Ok(State::startpos()),
+    }
+  }
+
+  /// Plays through the moves, checking that each is legal, and returns every position from the
+  /// start to the end of the game.
+  pub fn replay(&self) -> Result<Vec<State>, String> {
+    let mut state = self.start_state()?;
+    let mut positions = vec![state.clone()];
+    for (ply, recorded) in (1..).zip(&self.moves) {
+      if state.game_is_over() {
+        return Err(format!(
+          "Move {} at ply {} comes after the game ended",
+          recorded.m.to_uai(),
+          ply
+        ));
+      }
+      if !state.is_legal(recorded.m) {
+        return Err(format!(
+          "Illegal move {} at ply {}",
+          recorded.m.to_uai(),
+          ply
+        ));
+      }
+      state.make_move(recorded.m).unwrap();
+      positions.push(state.clone());
+    }
+    Ok(positions)
+  }
+
+  pub fn to_pgn(&self) -> String {
+    let mut pgn = String::new();
+    let mut tag = |name: &str, value: &str| {
+      let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+      pgn.push_str(&format!("[{} \"{}\"]\n", name, value));
+    };
+    tag("Event", &self.event);
+    tag("Date", &self.date);
+    tag("Black", &self.black);
+    tag("White", &self.white);
+    tag("Result", result_to_str(self.result));
+    if let Some(fen) = &self.fen {
+      tag("FEN", fen);
+    }
+    if let Some(time_control) = &self.time_control {
+      tag("TimeControl", time_control);
+    }
+    for (name, value) in &self.other_tags {
+      tag(name, value);
+    }
+    pgn.push('\n');
+
+    // Black moves first, so a move number goes before each of Black's moves. Lines only break
+    // between moves, so numbers stay with their moves.
+    let mut to_move = self.start_state().map(|state| state.to_move).unwrap_or(Color::Black);
+    let mut number = 1;
+    let mut tokens = Vec::new();
+    for (i, recorded) in self.moves.iter().enumerate() {
+      let mut token = match to_move {
+        Color::Black => format!("{}. ", number),
+        Color::White if i == 0 => format!("{}... ", number),
+        Color::White => String::new(),
+      };
+      token.push_str(&recorded.m.to_uai());
+      if let Some(score) = recorded.score {
+        token.push_str(&format!(" {{{:+.2}", score as f64 / 100.0));
+        if let Some(depth) = recorded.depth {
+          token.push_str(&format!("/{}", depth));
+        }
+        token.push('}');
+      }
+      tokens.push(token);
+      if to_move == Color::White {
+        number += 1;
+      }
+      to_move = to_move.other_player();
+    }
+    tokens.push(result_to_str(self.result).to_string());
+
+    let mut line_length = 0;
+    for token in tokens {
+      if line_length > 0 && line_length + 1 + token.len() > LINE_WIDTH {
+        pgn.push('\n');
+        line_length = 0;
+      } else if line_length > 0 {
+        pgn.push(' ');
+        line_length += 1;
+      }
+      line_length += token.len();
+      pgn.push_str(&token);
+    }
+    pgn.push('\n');
+    pgn
+  }
+}
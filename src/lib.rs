@@ -1,3 +1,18 @@
+pub mod baselines;
+pub mod bench;
+pub mod book;
+pub mod data;
+pub mod game;
+pub mod mcts;
+#[cfg(feature = "nnue")]
+pub mod nnue;
+pub mod openings;
+pub mod options;
+pub mod perft;
 pub mod rng;
 pub mod rules;
 pub mod search;
+pub mod skill;
+pub mod stats;
+pub mod timeman;
+pub mod uai;
@@ -0,0 +1,381 @@
+//! Monte Carlo tree search, as an alternative to the alpha-beta `Engine`.
+//!
+//! The tree is stored in an arena of nodes. Selection uses PUCT with priors from how many stones
+//! each move gains, and leaves are scored by random or greedy playouts to the end of the game.
+//! The tree is kept between moves, so the subtree under the moves actually played is reused.
+
+use crate::rng::Rng;
+use crate::rules::{Color, GameResult, Move, State};
+use crate::search::{
+  Evaluation, IterationInfo, SearchLimits, SearchLine, SearchObserver, SearchStats, Searcher,
+  StopHandle,
+};
+use crate::timeman::TimeManager;
+
+/// Playouts that run this long are scored by counting stones.
+const MAX_PLAYOUT_PLIES: usize = 200;
+
+/// We stop growing the tree past this many nodes, but keep running playouts.
+const MAX_TREE_NODES: usize = 1 << 22;
+
+/// How often a search reports its progress.
+const REPORT_INTERVAL_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Playout {
+  /// Uniformly random moves.
+  Random,
+  /// The move that gains the most stones, breaking ties randomly.
+  Greedy,
+}
+
+struct Node {
+  /// The move that led to this node.
+  m:        Move,
+  state:    State,
+  prior:    f32,
+  children: Vec<usize>,
+  expanded: bool,
+  visits:   u32,
+  /// Total playout reward from the point of view of the player who made `m`.
+  reward:   f64,
+  draws:    u32,
+}
+
+impl Node {
+  fn new(m: Move, state: State, prior: f32) -> Node {
+    Node {
+      m,
+      state,
+      prior,
+      children: Vec::new(),
+      expanded: false,
+      visits: 0,
+      reward: 0.0,
+      draws: 0,
+    }
+  }
+}
+
+pub struct Mcts {
+  rng:                  Rng,
+  nodes:                Vec<Node>,
+  pub playout:          Playout,
+  /// The PUCT exploration constant.
+  pub exploration:      f64,
+  /// Milliseconds subtracted from every time limit, to cover communication delays.
+  pub move_overhead_ms: i32,
+  do_stop:              StopHandle,
+  observer:             Option<Box<dyn SearchObserver>>,
+}
+
+/// Converts a win rate into a centistone-like score, so it can be reported like the alpha-beta
+/// engine's scores.
+fn win_rate_to_score(win_rate: f64) -> Evaluation {
+  let win_rate = win_rate.clamp(0.001, 0.999);
+  (400.0 * (win_rate / (1.0 - win_rate)).log10()) as Evaluation
+}
+
+impl Mcts {
+  pub fn new(seed: u64) -> Mcts {
+    Mcts {
+      rng:              Rng::new(seed),
+      nodes:            vec![Node::new(Move::PASS, State::new(), 1.0)],
+      playout:          Playout::Random,
+      exploration:      1.5,
+      move_overhead_ms: 0,
+      do_stop:          StopHandle::new(),
+      observer:         None,
+    }
+  }
+
+  /// Reports the progress of the following searches to `observer`, or to nobody with None.
+  pub fn set_observer(&mut self, observer: Option<Box<dyn SearchObserver>>) {
+    self.observer = observer;
+  }
+
+  /// The most visited path from the root.
+  fn principal_variation(&self) -> Vec<Move> {
+    let mut pv = Vec::new();
+    let mut node = &self.nodes[0];
+    while let Some(&child) = node.children.iter().max_by_key(|c| self.nodes[**c].visits) {
+      node = &self.nodes[child];
+      if node.visits == 0 {
+        break;
+      }
+      pv.push(node.m);
+    }
+    pv
+  }
+
+  /// The most visited root move's line, scored by its win rate. None if the root has no moves.
+  fn best_line(&self) -> Option<SearchLine> {
+    let root = &self.nodes[0];
+    let best_child = root.children.iter().max_by_key(|c| self.nodes[**c].visits)?;
+    let child = &self.nodes[*best_child];
+    // Without any playouts, one move is as good as another.
+    if child.visits == 0 {
+      return Some(SearchLine {
+        depth: 1,
+        score: 0,
+        wdl:   None,
+        pv:    vec![child.m],
+      });
+    }
+    let visits = child.visits as f64;
+    // Draws count half towards the reward.
+    let wins = child.reward - child.draws as f64 / 2.0;
+    // Rounding down, so the three add up to a thousand.
+    let per_thousand = |count: f64| (1000.0 * count / visits) as u32;
+    let (wins, draws) = (per_thousand(wins), per_thousand(child.draws as f64));
+    let pv = self.principal_variation();
+    Some(SearchLine {
+      depth: pv.len() as u16,
+      score: win_rate_to_score(child.reward / visits),
+      wdl: Some([wins, draws, 1000 - wins - draws]),
+      pv,
+    })
+  }
+
+  /// Reports the best line so far as a completed iteration.
+  fn report_progress(&mut self, playouts: u64, time_ms: u64) {
+    let line = match self.best_line() {
+      Some(line) => line,
+      None => return,
+    };
+    if let Some(observer) = &mut self.observer {
+      observer.iteration_completed(&IterationInfo {
+        depth: line.depth,
+        nodes: playouts,
+        time_ms,
+        lines: vec![line],
+      });
+    }
+  }
+
+  /// The root is always the first node in the arena.
+  pub fn state(&self) -> &State {
+    &self.nodes[0].state
+  }
+
+  /// Sets the position to search, reusing the tree if the position is at most two plies below
+  /// the current root.
+  pub fn set_position(&mut self, state: State) {
+    if self.nodes[0].state == state {
+      return;
+    }
+    for &child in &self.nodes[0].children {
+      if self.nodes[child].state == state {
+        return self.reroot(child);
+      }
+      for &grandchild in &self.nodes[child].children {
+        if self.nodes[grandchild].state == state {
+          return self.reroot(grandchild);
+        }
+      }
+    }
+    self.nodes = vec![Node::new(Move::PASS, state, 1.0)];
+  }
+
+  pub fn make_move(&mut self, m: Move) -> Result<(), &'static str> {
+    let mut state = self.state().clone();
+    state.make_move(m)?;
+    self.set_position(state);
+    Ok(())
+  }
+
+  /// Makes `new_root` the root, compacting its subtree into a fresh arena.
+  fn reroot(&mut self, new_root: usize) {
+    let mut old_nodes = std::mem::take(&mut self.nodes).into_iter().map(Some).collect::<Vec<_>>();
+    let mut stack: Vec<(usize, Option<usize>)> = vec![(new_root, None)];
+    while let Some((old_index, parent)) = stack.pop() {
+      let mut node = old_nodes[old_index].take().unwrap();
+      let old_children = std::mem::take(&mut node.children);
+      let new_index = self.nodes.len();
+      self.nodes.push(node);
+      if let Some(parent) = parent {
+        self.nodes[parent].children.push(new_index);
+      }
+      // Push in reverse so children keep their order.
+      stack.extend(old_children.into_iter().rev().map(|c| (c, Some(new_index))));
+    }
+  }
+
+  fn expand(&mut self, index: usize) {
+    let state = self.nodes[index].state.clone();
+    let mut moves = Vec::new();
+    state.move_gen(&mut moves);
+    // Priors are a softmax over the stones each move gains.
+    let weights = moves.iter().map(|m| (state.stone_gain(*m) as f32).exp()).collect::<Vec<_>>();
+    let total: f32 = weights.iter().sum();
+    for (m, weight) in moves.into_iter().zip(weights) {
+      let mut child_state = state.clone();
+      child_state.make_move(m).unwrap();
+      let child = self.nodes.len();
+      self.nodes[index].children.push(child);
+      self.nodes.push(Node::new(m, child_state, weight / total));
+    }
+    self.nodes[index].expanded = true;
+  }
+
+  fn select_child(&self, index: usize) -> usize {
+    let node = &self.nodes[index];
+    let sqrt_visits = (node.visits as f64).sqrt();
+    let puct = |child: &Node| {
+      // Unvisited children are assumed to be even.
+      let q = match child.visits {
+        0 => 0.5,
+        n => child.reward / n as f64,
+      };
+      q + self.exploration * child.prior as f64 * sqrt_visits / (1 + child.visits) as f64
+    };
+    *node
+      .children
+      .iter()
+      .max_by(|a, b| puct(&self.nodes[**a]).total_cmp(&puct(&self.nodes[**b])))
+      .unwrap()
+  }
+
+  fn run_playout(&self, state: &State) -> GameResult {
+    let mut state = state.clone();
+    let mut moves = Vec::new();
+    for _ in 0..MAX_PLAYOUT_PLIES {
+      if state.game_is_over() {
+        break;
+      }
+      moves.clear();
+      state.move_gen(&mut moves);
+      let m = match self.playout {
+        Playout::Random => moves[self.rng.generate_range(moves.len() as u32) as usize],
+        Playout::Greedy => {
+          let best_gain = moves.iter().map(|m| state.stone_gain(*m)).max().unwrap();
+          moves.retain(|m| state.stone_gain(*m) == best_gain);
+          moves[self.rng.generate_range(moves.len() as u32) as usize]
+        }
+      };
+      state.make_move(m).unwrap();
+    }
+    state.result_by_material()
+  }
+
+  /// Runs one iteration of selection, expansion, playout and backpropagation.
+  fn iterate(&mut self) {
+    let mut path = vec![0];
+    let mut index = 0;
+    while self.nodes[index].expanded && !self.nodes[index].children.is_empty() {
+      index = self.select_child(index);
+      path.push(index);
+    }
+    if !self.nodes[index].state.game_is_over() && self.nodes.len() < MAX_TREE_NODES {
+      self.expand(index);
+    }
+    let result = self.run_playout(&self.nodes[index].state);
+    for index in path {
+      let node = &mut self.nodes[index];
+      node.visits += 1;
+      node.draws += (result == GameResult::Draw) as u32;
+      // The player who made the move into this node is the one not to move now.
+      let mover = node.state.to_move.other_player();
+      node.reward += match (result, mover) {
+        (GameResult::Draw, _) => 0.5,
+        (GameResult::BlackWins, Color::Black) => 1.0,
+        (GameResult::WhiteWins, Color::White) => 1.0,
+        _ => 0.0,
+      };
+    }
+  }
+
+  /// Searches the root position. The depth limit is ignored, and the node limit counts playouts.
+  pub fn run(&mut self, limits: &SearchLimits) -> (Evaluation, Option<Move>) {
+    let start = std::time::Instant::now();
+    self.do_stop.reset();
+    // Playouts can stop at any time, so we aim for the optimum and ignore iteration rules.
+    let time_manager = match (limits.movetime_ms, limits.clock) {
+      (Some(movetime_ms), _) => Some(TimeManager::fixed(movetime_ms, self.move_overhead_ms)),
+      (None, Some((ms_on_clock, ms_increment))) => Some(TimeManager::for_clock(
+        self.state(),
+        ms_on_clock,
+        ms_increment,
+        limits.moves_to_go,
+        self.move_overhead_ms,
+      )),
+      (None, None) => None,
+    };
+    if let (Some(time_manager), Some(observer)) = (&time_manager, &mut self.observer) {
+      observer.debug(&format!("time optimum {} ms", time_manager.optimum_ms()));
+    }
+    let deadline =
+      time_manager.map(|t| start + std::time::Duration::from_millis(t.optimum_ms() as u64));
+    let max_playouts = match (limits.nodes, deadline) {
+      (Some(nodes), _) => nodes,
+      (None, Some(_)) => u64::MAX,
+      // With no limits at all, do a fixed amount of work rather than running forever.
+      (None, None) => 10_000,
+    };
+    let mut playouts = 0;
+    let mut next_report_ms = REPORT_INTERVAL_MS;
+    while playouts < max_playouts {
+      self.iterate();
+      playouts += 1;
+      if self.do_stop.is_stopped() {
+        break;
+      }
+      if deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline) {
+        break;
+      }
+      let time_ms = start.elapsed().as_millis() as u64;
+      if time_ms >= next_report_ms {
+        self.report_progress(playouts, time_ms);
+        next_report_ms = time_ms + REPORT_INTERVAL_MS;
+      }
+    }
+
+    let time_ms = start.elapsed().as_millis() as u64;
+    self.report_progress(playouts, time_ms);
+    let line = self.best_line();
+    let (score, best_move) = match &line {
+      Some(line) => (line.score, Some(line.pv[0])),
+      None => (0, None),
+    };
+    if let Some(observer) = &mut self.observer {
+      observer.search_finished(&SearchStats {
+        depth: line.map_or(0, |line| line.depth),
+        nodes: playouts,
+        time_ms,
+        score,
+        best_move,
+      });
+    }
+    (score, best_move)
+  }
+}
+
+impl Searcher for Mcts {
+  fn name(&self) -> &'static str {
+    "mcts"
+  }
+
+  fn state(&self) -> &State {
+    Mcts::state(self)
+  }
+
+  fn set_position(&mut self, state: State) {
+    Mcts::set_position(self, state)
+  }
+
+  fn make_move(&mut self, m: Move) -> Result<(), &'static str> {
+    Mcts::make_move(self, m)
+  }
+
+  fn search(&mut self, limits: &SearchLimits) -> (Evaluation, Option<Move>) {
+    self.run(limits)
+  }
+
+  fn stop_handle(&self) -> StopHandle {
+    self.do_stop.clone()
+  }
+
+  fn reports_progress(&self) -> bool {
+    true
+  }
+}
@@ -0,0 +1,216 @@
+//! A small efficiently updatable neural network evaluation.
+//!
+//! The network has 147 binary inputs per perspective: "our" stones, "their" stones and gaps for
+//! each of the 49 cells. The first layer is an i16 accumulator that is kept up to date from each
+//! move's `MoveDelta`, so only the changed cells cost anything. The output layer takes the clipped
+//! accumulators of the side to move and the other side and applies i8 weights.
+//!
+//! Weight files are little-endian: the magic `SNPNNUE1`, the hidden size as a u32, then the
+//! feature weights as i16 `[INPUTS][hidden]`, the feature biases as i16 `[hidden]`, the output
+//! weights as i8 `[2 * hidden]` and finally the output bias as an i32.
+
+use crate::rng::Rng;
+use crate::rules::{Color, MoveDelta, State};
+
+pub const INPUTS: usize = 3 * 49;
+
+const MAGIC: &[u8; 8] = b"SNPNNUE1";
+
+/// Accumulator activations are clipped to [0, QA].
+const QA: i32 = 255;
+/// Output weights are fixed point with this scale.
+const QB: i32 = 64;
+/// Converts the network output into centistones.
+const OUTPUT_SCALE: i32 = 400;
+
+/// Maps a bitboard position to a 0..49 cell index.
+fn cell_index(pos: u32) -> usize {
+  (pos % 8 + 7 * (pos / 8)) as usize
+}
+
+fn our_stone_feature(pos: u32) -> usize {
+  cell_index(pos)
+}
+
+fn their_stone_feature(pos: u32) -> usize {
+  49 + cell_index(pos)
+}
+
+fn gap_feature(pos: u32) -> usize {
+  98 + cell_index(pos)
+}
+
+fn for_each_bit(mut bitboard: u64, mut f: impl FnMut(u32)) {
+  while bitboard != 0 {
+    f(bitboard.trailing_zeros());
+    bitboard &= bitboard - 1;
+  }
+}
+
+pub struct Network {
+  hidden:          usize,
+  feature_weights: Vec<i16>,
+  feature_biases:  Vec<i16>,
+  output_weights:  Vec<i8>,
+  output_bias:     i32,
+}
+
+impl Network {
+  pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+    if bytes.len() < 12 || &bytes[..8] != MAGIC {
+      return Err("Not an NNUE weight file".to_string());
+    }
+    let hidden = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+    let expected = 12 + 2 * INPUTS * hidden + 2 * hidden + 2 * hidden + 4;
+    if bytes.len() != expected {
+      return Err(format!(
+        "Expected {} bytes for hidden size {}, got {}",
+        expected,
+        hidden,
+        bytes.len()
+      ));
+    }
+    let mut offset = 12;
+    let mut read_i16s = |count: usize| {
+      let values = bytes[offset..offset + 2 * count]
+        .chunks_exact(2)
+        .map(|c| i16::from_le_bytes([c[0], c[1]]))
+        .collect::<Vec<_>>();
+      offset += 2 * count;
+      values
+    };
+    let feature_weights = read_i16s(INPUTS * hidden);
+    let feature_biases = read_i16s(hidden);
+    let output_weights = bytes[offset..offset + 2 * hidden].iter().map(|b| *b as i8).collect();
+    offset += 2 * hidden;
+    let output_bias = i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    Ok(Network {
+      hidden,
+      feature_weights,
+      feature_biases,
+      output_weights,
+      output_bias,
+    })
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend((self.hidden as u32).to_le_bytes());
+    for w in self.feature_weights.iter().chain(&self.feature_biases) {
+      bytes.extend(w.to_le_bytes());
+    }
+    bytes.extend(self.output_weights.iter().map(|w| *w as u8));
+    bytes.extend(self.output_bias.to_le_bytes());
+    bytes
+  }
+
+  pub fn load(path: &str) -> Result<Network, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    Network::from_bytes(&bytes)
+  }
+
+  /// A network with small random weights, for testing the inference code.
+  pub fn random(hidden: usize, rng: &Rng) -> Network {
+    let small = |range: u32| rng.generate_range(2 * range + 1) as i32 - range as i32;
+    Network {
+      hidden,
+      feature_weights: (0..INPUTS * hidden).map(|_| small(64) as i16).collect(),
+      feature_biases: (0..hidden).map(|_| small(64) as i16).collect(),
+      output_weights: (0..2 * hidden).map(|_| small(127) as i8).collect(),
+      output_bias: small(1000),
+    }
+  }
+
+  pub fn hidden_size(&self) -> usize {
+    self.hidden
+  }
+
+  fn feature_row(&self, feature: usize) -> &[i16] {
+    &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+  }
+
+  /// Evaluates a position from the side to move's point of view, in centistones.
+  pub fn evaluate(&self, accumulator: &Accumulator, to_move: Color) -> i32 {
+    let (ours, theirs) = match to_move {
+      Color::Black => (&accumulator.black, &accumulator.white),
+      Color::White => (&accumulator.white, &accumulator.black),
+    };
+    // Files can declare any hidden size, so sum in i64 where big networks can't overflow.
+    let mut output: i64 = 0;
+    for (a, w) in ours.iter().chain(theirs.iter()).zip(&self.output_weights) {
+      output += ((*a as i32).clamp(0, QA) * *w as i32) as i64;
+    }
+    let scaled = (output + self.output_bias as i64) * OUTPUT_SCALE as i64 / (QA * QB) as i64;
+    scaled.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+  }
+}
+
+/// The first layer activations, from black's and white's perspectives.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Accumulator {
+  black: Vec<i16>,
+  white: Vec<i16>,
+}
+
+impl Accumulator {
+  /// Computes the accumulator from scratch.
+  pub fn refresh(network: &Network, state: &State) -> Accumulator {
+    let mut accumulator = Accumulator {
+      black: network.feature_biases.clone(),
+      white: network.feature_biases.clone(),
+    };
+    for (perspective, ours, theirs) in [
+      (Color::Black, state.black_stones, state.white_stones),
+      (Color::White, state.white_stones, state.black_stones),
+    ] {
+      let values = accumulator.perspective_mut(perspective);
+      let mut add = |feature: usize| {
+        for (v, w) in values.iter_mut().zip(network.feature_row(feature)) {
+          *v = v.wrapping_add(*w);
+        }
+      };
+      for_each_bit(ours, |pos| add(our_stone_feature(pos)));
+      for_each_bit(theirs, |pos| add(their_stone_feature(pos)));
+      for_each_bit(state.gaps, |pos| add(gap_feature(pos)));
+    }
+    accumulator
+  }
+
+  fn perspective_mut(&mut self, perspective: Color) -> &mut Vec<i16> {
+    match perspective {
+      Color::Black => &mut self.black,
+      Color::White => &mut self.white,
+    }
+  }
+
+  /// Applies the changes made by a move. Gaps never change, so only stone features are touched.
+  pub fn update(&mut self, network: &Network, delta: &MoveDelta) {
+    let mover = match delta.mover {
+      Some(mover) => mover,
+      // Passes don't change the board.
+      None => return,
+    };
+    for perspective in [Color::Black, Color::White] {
+      let values = self.perspective_mut(perspective);
+      let mover_feature = |pos| match perspective == mover {
+        true => our_stone_feature(pos),
+        false => their_stone_feature(pos),
+      };
+      let other_feature = |pos| match perspective == mover {
+        true => their_stone_feature(pos),
+        false => our_stone_feature(pos),
+      };
+      let mut apply = |feature: usize, sign: i16| {
+        for (v, w) in values.iter_mut().zip(network.feature_row(feature)) {
+          *v = v.wrapping_add(sign.wrapping_mul(*w));
+        }
+      };
+      for_each_bit(delta.placed, |pos| apply(mover_feature(pos), 1));
+      for_each_bit(delta.removed, |pos| apply(mover_feature(pos), -1));
+      for_each_bit(delta.flipped, |pos| {
+        apply(other_feature(pos), -1);
+        apply(mover_feature(pos), 1);
+      });
+    }
+  }
+}
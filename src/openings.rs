@@ -0,0 +1,180 @@
+//! Opening positions for matches and data generation: the standard corner stones on boards with
+//! symmetric gap patterns, optionally followed by a few random plies, and kept only if a shallow
+//! search finds them roughly balanced.
+
+use std::collections::HashSet;
+
+use crate::rng::Rng;
+use crate::rules::{State, ALL_CELLS_MASK};
+use crate::search::{Engine, Evaluation};
+
+/// How a gap pattern is symmetric. The corner stones alternate colours, so the mirrors map each
+/// side's stones onto the other's, and neither side starts with a better board. A rotation maps
+/// each side's stones onto its own, which leaves the fairness search to catch lopsided boards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+  /// Mirrored across the horizontal axis, rank 1 onto rank 7.
+  Horizontal,
+  /// Mirrored across the vertical axis, file a onto file g.
+  Vertical,
+  /// Rotated half a turn about the centre.
+  Rotational,
+}
+
+impl Symmetry {
+  pub const ALL: [Symmetry; 3] = [
+    Symmetry::Horizontal,
+    Symmetry::Vertical,
+    Symmetry::Rotational,
+  ];
+
+  pub fn parse(name: &str) -> Result<Symmetry, String> {
+    match name {
+      "horizontal" => Ok(Symmetry::Horizontal),
+      "vertical" => Ok(Symmetry::Vertical),
+      "rotational" => Ok(Symmetry::Rotational),
+      _ => Err(format!(
+        "Unknown symmetry: {} (expected horizontal, vertical or rotational)",
+        name
+      )),
+    }
+  }
+
+  /// Maps every cell of a bitboard to its mirror image.
+  pub fn apply(self, bitboard: u64) -> u64 {
+    // Rows are bytes, so reversing the bytes flips ranks, and reversing the bits too flips files.
+    // The shifts account for the unused eighth row and column.
+    let flip_ranks = |b: u64| b.swap_bytes() >> 8;
+    let flip_files = |b: u64| b.reverse_bits().swap_bytes() >> 1;
+    let mirrored = match self {
+      Symmetry::Horizontal => flip_ranks(bitboard),
+      Symmetry::Vertical => flip_files(bitboard),
+      Symmetry::Rotational => flip_ranks(flip_files(bitboard)),
+    };
+    mirrored & ALL_CELLS_MASK
+  }
+}
+
+/// Places up to `max_gaps` gaps at random, along with their mirror images, keeping the corners
+/// free for the starting stones.
+pub fn symmetric_gaps(symmetry: Symmetry, max_gaps: u32, rng: &Rng) -> u64 {
+  let corners = State::startpos().black_stones | State::startpos().white_stones;
+  let mut gaps: u64 = 0;
+  // A pair that doesn't fit can leave nothing that does, so give up after a while.
+  for _ in 0..1000 {
+    if gaps.count_ones() >= max_gaps {
+      break;
+    }
+    let cell = 1 << (rng.generate_range(7) + 8 * rng.generate_range(7));
+    let pair = cell | symmetry.apply(cell);
+    if pair & corners == 0 && (gaps | pair).count_ones() <= max_gaps {
+      gaps |= pair;
+    }
+  }
+  gaps
+}
+
+#[derive(Clone, Debug)]
+pub struct OpeningConfig {
+  /// Each opening picks one of these at random.
+  pub symmetries:   Vec<Symmetry>,
+  pub min_gaps:     u32,
+  pub max_gaps:     u32,
+  pub random_plies: usize,
+  /// The depth of the fairness search.
+  pub depth:        u16,
+  /// Openings the fairness search scores further from zero than this are thrown away.
+  pub max_score:    Evaluation,
+}
+
+impl Default for OpeningConfig {
+  fn default() -> OpeningConfig {
+    OpeningConfig {
+      symmetries:   Symmetry::ALL.to_vec(),
+      min_gaps:     0,
+      max_gaps:     8,
+      random_plies: 0,
+      depth:        4,
+      max_score:    100,
+    }
+  }
+}
+
+/// Makes one opening, or returns None if the random plies ended the game or the fairness search
+/// found it lopsided.
+pub fn random_opening(config: &OpeningConfig, rng: &Rng, engine: &mut Engine) -> Option<State> {
+  let symmetry = config.symmetries[rng.generate_range(config.symmetries.len() as u32) as usize];
+  let gap_range = config.max_gaps.saturating_sub(config.min_gaps) + 1;
+  let gaps = symmetric_gaps(
+    symmetry,
+    config.min_gaps + rng.generate_range(gap_range),
+    rng,
+  );
+  let mut state = State {
+    gaps,
+    ..State::startpos()
+  };
+  let mut moves = Vec::new();
+  for _ in 0..config.random_plies {
+    if state.game_is_over() {
+      return None;
+    }
+    moves.clear();
+    state.move_gen(&mut moves);
+    state.make_move(moves[rng.generate_range(moves.len() as u32) as usize]).unwrap();
+  }
+  if state.game_is_over() {
+    return None;
+  }
+  engine.set_position(state.clone());
+  let (score, _) = engine.run_depth(config.depth);
+  match score.abs() <= config.max_score {
+    true => Some(state),
+    false => None,
+  }
+}
+
+/// Makes up to `count` distinct openings. Fewer come back if too many tries turn out unfair or
+/// repeat an earlier opening.
+pub fn generate(config: &OpeningConfig, count: usize, seed: u64) -> Vec<State> {
+  let rng = Rng::new(seed);
+  let mut engine = Engine::new(seed);
+  // Noise would make the fairness check depend on luck.
+  engine.noise = 0;
+  let mut seen = HashSet::new();
+  let mut openings = Vec::new();
+  for _ in 0..count * 100 {
+    if openings.len() >= count {
+      break;
+    }
+    if let Some(state) = random_opening(config, &rng, &mut engine) {
+      if seen.insert(state.hash_with_gaps()) {
+        openings.push(state);
+      }
+    }
+  }
+  openings
+}
+
+/// Reads openings, one FEN or EPD per line. EPD operations after the side to move are ignored.
+pub fn load(path: &str) -> Result<Vec<State>, String> {
+  let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+  let mut openings = Vec::new();
+  for line in text.lines().map(|line| line.trim()) {
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let fields = line.split_whitespace().take(2).collect::<Vec<_>>();
+    openings.push(State::from_fen(&fields.join(" ")).map_err(|e| format!("{}: {}", line, e))?);
+  }
+  match openings.is_empty() {
+    true => Err(format!("{}: No openings", path)),
+    false => Ok(openings),
+  }
+}
+
+/// Writes openings as FENs, one per line, which `load` reads back.
+pub fn save(path: &str, openings: &[State]) -> Result<(), String> {
+  let text = openings.iter().map(|state| format!("{}\n", state.to_fen())).collect::<String>();
+  std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))
+}
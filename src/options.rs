@@ -0,0 +1,261 @@
+//! Typed UAI options: their declarations, and parsing of the values sent with setoption.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionType {
+  Spin {
+    default: i64,
+    min:     i64,
+    max:     i64,
+  },
+  Check {
+    default: bool,
+  },
+  Combo {
+    default: String,
+    vars:    Vec<String>,
+  },
+  String {
+    default: String,
+  },
+  Button,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionValue {
+  Spin(i64),
+  Check(bool),
+  Combo(String),
+  String(String),
+  Button,
+}
+
+impl OptionValue {
+  pub fn as_spin(&self) -> Option<i64> {
+    match self {
+      OptionValue::Spin(x) => Some(*x),
+      _ => None,
+    }
+  }
+
+  pub fn as_check(&self) -> Option<bool> {
+    match self {
+      OptionValue::Check(x) => Some(*x),
+      _ => None,
+    }
+  }
+
+  /// The value of a combo or string option.
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      OptionValue::Combo(s) | OptionValue::String(s) => Some(s),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UaiOption {
+  pub name: String,
+  pub kind: OptionType,
+}
+
+impl UaiOption {
+  pub fn spin(name: &str, default: i64, min: i64, max: i64) -> UaiOption {
+    UaiOption {
+      name: name.to_string(),
+      kind: OptionType::Spin { default, min, max },
+    }
+  }
+
+  pub fn check(name: &str, default: bool) -> UaiOption {
+    UaiOption {
+      name: name.to_string(),
+      kind: OptionType::Check { default },
+    }
+  }
+
+  pub fn combo(name: &str, default: &str, vars: &[&str]) -> UaiOption {
+    UaiOption {
+      name: name.to_string(),
+      kind: OptionType::Combo {
+        default: default.to_string(),
+        vars:    vars.iter().map(|v| v.to_string()).collect(),
+      },
+    }
+  }
+
+  pub fn string(name: &str, default: &str) -> UaiOption {
+    UaiOption {
+      name: name.to_string(),
+      kind: OptionType::String {
+        default: default.to_string(),
+      },
+    }
+  }
+
+  pub fn button(name: &str) -> UaiOption {
+    UaiOption {
+      name: name.to_string(),
+      kind: OptionType::Button,
+    }
+  }
+
+  pub fn default_value(&self) -> OptionValue {
+    match &self.kind {
+      OptionType::Spin { default, .. } => OptionValue::Spin(*default),
+      OptionType::Check { default } => OptionValue::Check(*default),
+      OptionType::Combo { default, .. } => OptionValue::Combo(default.clone()),
+      OptionType::String { default } => OptionValue::String(default.clone()),
+      OptionType::Button => OptionValue::Button,
+    }
+  }
+
+  /// Parses a declaration, the arguments of an `option` line after `option`.
+  pub fn parse(args: &[&str]) -> Result<UaiOption, String> {
+    // Values run until the next keyword, since names, defaults and vars may contain spaces.
+    const KEYWORDS: [&str; 6] = ["name", "type", "default", "min", "max", "var"];
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    for arg in args {
+      match (KEYWORDS.contains(arg), fields.last_mut()) {
+        (true, _) => fields.push((arg, String::new())),
+        (false, Some((_, value))) if value.is_empty() => value.push_str(arg),
+        (false, Some((_, value))) => value.push_str(&format!(" {}", arg)),
+        (false, None) => return Err(format!("Malformed option: {}", args.join(" "))),
+      }
+    }
+    let field = |key| fields.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str());
+    let name = match field("name") {
+      Some(name) if !name.is_empty() => name.to_string(),
+      _ => return Err(format!("Option without a name: {}", args.join(" "))),
+    };
+    let default = || field("default").ok_or_else(|| format!("Option {} has no default", name));
+    let number = |key| match field(key).map(|v| v.parse::<i64>()) {
+      Some(Ok(x)) => Ok(x),
+      _ => Err(format!("Missing or invalid {} for option {}", key, name)),
+    };
+    let kind = match field("type") {
+      Some("spin") => OptionType::Spin {
+        default: number("default")?,
+        min:     number("min")?,
+        max:     number("max")?,
+      },
+      Some("check") => match default()? {
+        "true" => OptionType::Check { default: true },
+        "false" => OptionType::Check { default: false },
+        other => return Err(format!("Invalid default for option {}: {}", name, other)),
+      },
+      Some("combo") => OptionType::Combo {
+        default: default()?.to_string(),
+        vars:    fields.iter().filter(|(k, _)| *k == "var").map(|(_, v)| v.clone()).collect(),
+      },
+      Some("string") => OptionType::String {
+        default: match default()? {
+          "<empty>" => String::new(),
+          default => default.to_string(),
+        },
+      },
+      Some("button") => OptionType::Button,
+      _ => return Err(format!("Missing or unknown type for option {}", name)),
+    };
+    Ok(UaiOption { name, kind })
+  }
+
+  /// Parses and validates the value given in a setoption command.
+  pub fn parse_value(&self, value: &str) -> Result<OptionValue, String> {
+    match &self.kind {
+      OptionType::Spin { min, max, .. } => match value.parse::<i64>() {
+        Ok(x) if (*min..=*max).contains(&x) => Ok(OptionValue::Spin(x)),
+        _ => Err(format!(
+          "{} must be an integer from {} to {}, got {}",
+          self.name, min, max, value
+        )),
+      },
+      OptionType::Check { .. } => match value {
+        "true" => Ok(OptionValue::Check(true)),
+        "false" => Ok(OptionValue::Check(false)),
+        _ => Err(format!(
+          "{} must be true or false, got {}",
+          self.name, value
+        )),
+      },
+      OptionType::Combo { vars, .. } => match vars.iter().find(|v| v.eq_ignore_ascii_case(value)) {
+        Some(var) => Ok(OptionValue::Combo(var.clone())),
+        None => Err(format!(
+          "{} must be one of {}, got {}",
+          self.name,
+          vars.join(", "),
+          value
+        )),
+      },
+      OptionType::String { .. } => match value {
+        // UAI has no way to send an empty string, so GUIs send this instead.
+        "<empty>" => Ok(OptionValue::String(String::new())),
+        _ => Ok(OptionValue::String(value.to_string())),
+      },
+      OptionType::Button => Ok(OptionValue::Button),
+    }
+  }
+}
+
+/// Formats the declaration sent in response to `uai`.
+impl fmt::Display for UaiOption {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "option name {} type ", self.name)?;
+    match &self.kind {
+      OptionType::Spin { default, min, max } => {
+        write!(f, "spin default {} min {} max {}", default, min, max)
+      }
+      OptionType::Check { default } => write!(f, "check default {}", default),
+      OptionType::Combo { default, vars } => {
+        write!(f, "combo default {}", default)?;
+        vars.iter().try_for_each(|v| write!(f, " var {}", v))
+      }
+      OptionType::String { default } => match default.is_empty() {
+        true => write!(f, "string default <empty>"),
+        false => write!(f, "string default {}", default),
+      },
+      OptionType::Button => write!(f, "button"),
+    }
+  }
+}
+
+/// The options an engine declares, along with their current values.
+pub struct OptionsRegistry {
+  options: Vec<(UaiOption, OptionValue)>,
+}
+
+impl OptionsRegistry {
+  pub fn new(options: Vec<UaiOption>) -> OptionsRegistry {
+    OptionsRegistry {
+      options: options
+        .into_iter()
+        .map(|o| {
+          let value = o.default_value();
+          (o, value)
+        })
+        .collect(),
+    }
+  }
+
+  pub fn declarations(&self) -> impl Iterator<Item = &UaiOption> {
+    self.options.iter().map(|(o, _)| o)
+  }
+
+  /// Looks up an option's current value. Option names are case insensitive.
+  pub fn get(&self, name: &str) -> Option<&OptionValue> {
+    self.options.iter().find(|(o, _)| o.name.eq_ignore_ascii_case(name)).map(|(_, v)| v)
+  }
+
+  /// Validates and stores a value, returning the option's canonical name and the parsed value.
+  pub fn set(&mut self, name: &str, value: &str) -> Result<(String, OptionValue), String> {
+    let (option, current) = self
+      .options
+      .iter_mut()
+      .find(|(o, _)| o.name.eq_ignore_ascii_case(name))
+      .ok_or_else(|| format!("No such option: {}", name))?;
+    *current = option.parse_value(value)?;
+    Ok((option.name.clone(), current.clone()))
+  }
+}
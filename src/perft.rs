@@ -0,0 +1,107 @@
+//! Move generator checks: counting the leaves of the game tree to a fixed depth.
+//!
+//! Finished games have no moves, and a player with no moves but a game still going must pass,
+//! which counts as one move.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::rng::RNG_MULT;
+use crate::rules::{Move, State};
+
+/// Remembers subtree counts, keyed by position hash and depth. Like the search's move table, it's
+/// shared between threads without locks, with each entry's key XORed with its count.
+pub struct PerftCache {
+  entries: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl PerftCache {
+  pub fn new(size_mb: usize) -> PerftCache {
+    let count = (size_mb << 20) / std::mem::size_of::<(AtomicU64, AtomicU64)>();
+    PerftCache {
+      entries: (0..count.max(1)).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect(),
+    }
+  }
+
+  /// One cache may see several gap layouts, so the key includes the gaps.
+  fn key(state: &State, depth: u16) -> u64 {
+    state.hash_with_gaps() ^ (depth as u64).wrapping_mul(RNG_MULT)
+  }
+
+  fn get(&self, key: u64) -> Option<u64> {
+    let entry = &self.entries[(key % self.entries.len() as u64) as usize];
+    let count = entry.1.load(Ordering::Relaxed);
+    match entry.0.load(Ordering::Relaxed) ^ count == key {
+      true => Some(count),
+      false => None,
+    }
+  }
+
+  fn set(&self, key: u64, count: u64) {
+    let entry = &self.entries[(key % self.entries.len() as u64) as usize];
+    entry.0.store(key ^ count, Ordering::Relaxed);
+    entry.1.store(count, Ordering::Relaxed);
+  }
+}
+
+fn legal_moves(state: &State) -> Vec<Move> {
+  let mut moves = Vec::new();
+  if !state.game_is_over() {
+    state.move_gen(&mut moves);
+  }
+  moves
+}
+
+/// Counts the positions `depth` plies below `state`.
+pub fn perft(state: &State, depth: u16, cache: Option<&PerftCache>) -> u64 {
+  if depth == 0 {
+    return 1;
+  }
+  let moves = legal_moves(state);
+  // Bulk counting: the moves at the last ply don't need to be made.
+  if depth == 1 {
+    return moves.len() as u64;
+  }
+  let key = PerftCache::key(state, depth);
+  if let Some(count) = cache.and_then(|cache| cache.get(key)) {
+    return count;
+  }
+  let mut total = 0;
+  for m in moves {
+    let mut new_state = state.clone();
+    new_state.make_move(m).unwrap();
+    total += perft(&new_state, depth - 1, cache);
+  }
+  if let Some(cache) = cache {
+    cache.set(key, total);
+  }
+  total
+}
+
+/// Counts the positions below each root move, splitting the root moves between threads.
+pub fn divide(
+  state: &State,
+  depth: u16,
+  threads: usize,
+  cache: Option<&PerftCache>,
+) -> Vec<(Move, u64)> {
+  let moves = legal_moves(state);
+  if depth == 0 {
+    return Vec::new();
+  }
+  let counts = moves.iter().map(|_| AtomicU64::new(0)).collect::<Vec<_>>();
+  let next = AtomicU64::new(0);
+  std::thread::scope(|scope| {
+    for _ in 0..threads.max(1) {
+      scope.spawn(|| loop {
+        let i = next.fetch_add(1, Ordering::Relaxed) as usize;
+        if i >= moves.len() {
+          break;
+        }
+        let mut new_state = state.clone();
+        new_state.make_move(moves[i]).unwrap();
+        counts[i].store(perft(&new_state, depth - 1, cache), Ordering::Relaxed);
+      });
+    }
+  });
+  moves.into_iter().zip(counts.into_iter().map(|c| c.into_inner())).collect()
+}
@@ -2,9 +2,9 @@ use crate::rng::RNG_MULT;
 
 include!(concat!(env!("OUT_DIR"), "/tables.rs"));
 
-const ALL_CELLS_MASK: u64 = 0x7f7f7f7f7f7f7f;
+pub const ALL_CELLS_MASK: u64 = 0x7f7f7f7f7f7f7f;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
   Black,
   White,
@@ -19,7 +19,27 @@ impl Color {
   }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+  BlackWins,
+  WhiteWins,
+  Draw,
+}
+
+/// The cells changed by a move, for incrementally updated evaluations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MoveDelta {
+  /// The mover that made the changes.
+  pub mover:   Option<Color>,
+  /// The cell the new stone was placed on, as a bitboard.
+  pub placed:  u64,
+  /// The cell a stone jumped away from, as a bitboard.
+  pub removed: u64,
+  /// The opponent stones that were converted to the mover's color.
+  pub flipped: u64,
+}
+
+#[derive(Clone, PartialEq, Eq)]
 pub struct State {
   pub black_stones: u64,
   pub white_stones: u64,
@@ -31,19 +51,18 @@ pub struct State {
 pub struct Spot(u8);
 
 impl Spot {
-  pub fn from_uai(s: &str) -> Spot {
-    let mut chars = s.chars();
-    let letter = chars.next().unwrap();
-    let number = chars.next().unwrap();
-    if chars.next().is_some() {
-      panic!("Invalid spot: {}", s);
+  pub fn from_uai(s: &str) -> Result<Spot, String> {
+    let bytes = s.as_bytes();
+    if bytes.len() != 2 {
+      return Err(format!("Invalid spot: {}", s));
     }
-    let letter = letter as u8 - b'a';
-    let number = number as u8 - b'1';
+    let letter = bytes[0].wrapping_sub(b'a');
+    let number = bytes[1].wrapping_sub(b'1');
     if letter > 6 || number > 6 {
-      panic!("Invalid spot: {}", s);
+      return Err(format!("Invalid spot: {}", s));
     }
-    Spot(letter + 8 * number)
+    // Rank 7 is the first row of the board.
+    Ok(Spot(letter + 8 * (6 - number)))
   }
 
   pub fn to_uai(self) -> String {
@@ -75,23 +94,35 @@ impl Move {
     to:   Spot(255),
   };
 
-  pub fn from_uai(uai: &str) -> Move {
+  pub fn from_uai(uai: &str) -> Result<Move, String> {
     if uai == "0000" {
-      return Move::PASS;
+      return Ok(Move::PASS);
     }
     match uai.len() {
-      4 => Move {
-        from: Spot::from_uai(&uai[..2]),
-        to:   Spot::from_uai(&uai[2..]),
-      },
+      4 if uai.is_char_boundary(2) => Ok(Move {
+        from: Spot::from_uai(&uai[..2])?,
+        to:   Spot::from_uai(&uai[2..])?,
+      }),
       2 => {
-        let spot = Spot::from_uai(uai);
-        Move {
+        let spot = Spot::from_uai(uai)?;
+        Ok(Move {
           from: spot,
           to:   spot,
-        }
+        })
       }
-      _ => panic!("Invalid move: {}", uai),
+      _ => Err(format!("Invalid move: {}", uai)),
+    }
+  }
+
+  /// Packs the move into 16 bits, for storing in hash tables.
+  pub fn pack(self) -> u16 {
+    (self.from.0 as u16) << 8 | self.to.0 as u16
+  }
+
+  pub fn unpack(packed: u16) -> Move {
+    Move {
+      from: Spot((packed >> 8) as u8),
+      to:   Spot(packed as u8),
     }
   }
 
@@ -106,7 +137,25 @@ impl Move {
   }
 }
 
+impl Default for State {
+  fn default() -> State {
+    State::new()
+  }
+}
+
 impl State {
+  /// The standard starting position, `x5o/7/7/7/7/7/o5x x 0 1`.
+  pub const fn startpos() -> State {
+    State {
+      // a7 and g1.
+      black_stones: 1 | (1 << 54),
+      // g7 and a1.
+      white_stones: (1 << 6) | (1 << 48),
+      gaps:         0,
+      to_move:      Color::Black,
+    }
+  }
+
   pub fn new() -> State {
     State {
       black_stones: 0,
@@ -116,22 +165,37 @@ impl State {
     }
   }
 
+  /// The game ends when the board is full or one player has no stones left.
   pub fn game_is_over(&self) -> bool {
     (self.black_stones | self.white_stones | self.gaps) == ALL_CELLS_MASK
+      || self.black_stones == 0
+      || self.white_stones == 0
   }
 
-  pub fn get_winner(&self) -> Option<Color> {
+  /// Scores the position by counting stones, regardless of whether the game is over.
+  pub fn result_by_material(&self) -> GameResult {
+    let black_score = self.black_stones.count_ones();
+    let white_score = self.white_stones.count_ones();
+    match black_score.cmp(&white_score) {
+      std::cmp::Ordering::Less => GameResult::WhiteWins,
+      std::cmp::Ordering::Equal => GameResult::Draw,
+      std::cmp::Ordering::Greater => GameResult::BlackWins,
+    }
+  }
+
+  pub fn result(&self) -> Option<GameResult> {
     match self.game_is_over() {
       false => None,
-      true => {
-        let black_score = self.black_stones.count_ones();
-        let white_score = self.white_stones.count_ones();
-        match black_score.cmp(&white_score) {
-          std::cmp::Ordering::Less => Some(Color::White),
-          std::cmp::Ordering::Equal => panic!("Odd number of gaps lead to draw"),
-          std::cmp::Ordering::Greater => Some(Color::Black),
-        }
-      }
+      true => Some(self.result_by_material()),
+    }
+  }
+
+  /// Returns None if the game isn't over or was drawn.
+  pub fn get_winner(&self) -> Option<Color> {
+    match self.result() {
+      Some(GameResult::BlackWins) => Some(Color::Black),
+      Some(GameResult::WhiteWins) => Some(Color::White),
+      Some(GameResult::Draw) | None => None,
     }
   }
 
@@ -154,7 +218,7 @@ impl State {
           s.push(' ');
         }
       }
-      s.push_str("\n");
+      s.push('\n');
     }
     match self.to_move {
       Color::Black => {
@@ -167,6 +231,8 @@ impl State {
     s
   }
 
+  /// Hashes the stones and the side to move. Searches can use this, since the gaps never change
+  /// within a game.
   pub fn get_hash(&self) -> u64 {
     let mut hash = 0;
     macro_rules! hash_in(
@@ -178,20 +244,31 @@ impl State {
         hash ^= hash >> 37;
       }
     );
-    // We don't need to include the gaps, because they're fixed.
     hash_in!(self.black_stones);
     hash_in!(self.white_stones);
     hash_in!(self.to_move as u64);
     hash
   }
 
+  /// `get_hash` with the gaps mixed in, for tables that outlive one game, like opening books.
+  pub fn hash_with_gaps(&self) -> u64 {
+    let hash = (self.get_hash() ^ self.gaps).wrapping_mul(RNG_MULT);
+    hash ^ hash >> 37
+  }
+
   pub fn from_fen(fen: &str) -> Result<State, String> {
     let mut state = State::new();
     let mut chars = fen.chars();
     let mut i = 0;
-    while let Some(c) = chars.next() {
+    for c in chars.by_ref() {
+      // Every cell must land inside the board, not in the unused eighth column or past the end.
+      let row_room = 7 - i % 8;
       match c {
-        '1'..='7' => i += c as u8 - '1' as u8,
+        '1'..='7' | 'x' | 'o' | '-' if i >= 6 * 8 + 7 || row_room == 0 => {
+          return Err(format!("Row too long i={}", i));
+        }
+        '1'..='7' if c as u8 - b'0' > row_room => return Err(format!("Row too long i={}", i)),
+        '1'..='7' => i += c as u8 - b'1',
         'x' => state.black_stones |= 1 << i,
         'o' => state.white_stones |= 1 << i,
         '-' => state.gaps |= 1 << i,
@@ -218,6 +295,43 @@ impl State {
     Ok(state)
   }
 
+  /// Writes the position as a FEN. We don't track the move counters, so they're always `0 1`.
+  pub fn to_fen(&self) -> String {
+    let mut fen = String::new();
+    for y in 0..7 {
+      let mut empty = 0;
+      for x in 0..7 {
+        let mask = 1 << (y * 8 + x);
+        let c = if self.black_stones & mask != 0 {
+          'x'
+        } else if self.white_stones & mask != 0 {
+          'o'
+        } else if self.gaps & mask != 0 {
+          '-'
+        } else {
+          empty += 1;
+          continue;
+        };
+        if empty > 0 {
+          fen.push_str(&empty.to_string());
+          empty = 0;
+        }
+        fen.push(c);
+      }
+      if empty > 0 {
+        fen.push_str(&empty.to_string());
+      }
+      if y != 6 {
+        fen.push('/');
+      }
+    }
+    match self.to_move {
+      Color::Black => fen.push_str(" x 0 1"),
+      Color::White => fen.push_str(" o 0 1"),
+    }
+    fen
+  }
+
   pub fn move_gen(&self, moves: &mut Vec<Move>) {
     let unoccupied = !(self.black_stones | self.white_stones | self.gaps);
     let mut our_stones = match self.to_move {
@@ -240,6 +354,27 @@ impl State {
     }
   }
 
+  /// Checks a move against the legal moves. Passing is only legal with no other moves.
+  pub fn is_legal(&self, m: Move) -> bool {
+    let mut moves = Vec::new();
+    self.move_gen(&mut moves);
+    moves.contains(&m)
+  }
+
+  /// How many stones the player to move gains by making this move: one for a single move, plus
+  /// the captures.
+  pub fn stone_gain(&self, m: Move) -> u32 {
+    if m == Move::PASS {
+      return 0;
+    }
+    let opponent_stones = match self.to_move {
+      Color::Black => self.white_stones,
+      Color::White => self.black_stones,
+    };
+    let captures = (MOORE_MASK[m.to.0 as usize] & opponent_stones).count_ones();
+    captures + (m.from == m.to) as u32
+  }
+
   pub fn sanity_check(&self) {
     if self.black_stones & self.white_stones != 0 {
       panic!("Black and white stones overlap");
@@ -253,10 +388,20 @@ impl State {
   }
 
   pub fn make_move(&mut self, m: Move) -> Result<(), &'static str> {
+    self.make_move_delta(m).map(|_| ())
+  }
+
+  /// Makes a move, and returns which cells it changed.
+  pub fn make_move_delta(&mut self, m: Move) -> Result<MoveDelta, &'static str> {
     if m == Move::PASS {
       self.to_move = self.to_move.other_player();
-      return Ok(());
+      return Ok(MoveDelta::default());
     }
+    let mut delta = MoveDelta {
+      mover: Some(self.to_move),
+      placed: 1 << m.to.0,
+      ..MoveDelta::default()
+    };
     // Place the target stone.
     match self.to_move {
       Color::Black => self.black_stones |= 1 << m.to.0,
@@ -264,6 +409,7 @@ impl State {
     };
     // Remove the source stone if it's a double move.
     if m.from.0 != m.to.0 {
+      delta.removed = 1 << m.from.0;
       match self.to_move {
         Color::Black => self.black_stones &= !(1 << m.from.0),
         Color::White => self.white_stones &= !(1 << m.from.0),
@@ -271,6 +417,11 @@ impl State {
     }
     // Capture neighbors.
     let captures = MOORE_MASK[m.to.0 as usize] & (self.black_stones | self.white_stones);
+    delta.flipped = captures
+      & match self.to_move {
+        Color::Black => self.white_stones,
+        Color::White => self.black_stones,
+      };
     self.black_stones &= !captures;
     self.white_stones &= !captures;
     match self.to_move {
@@ -278,6 +429,6 @@ impl State {
       Color::White => self.white_stones |= captures,
     }
     self.to_move = self.to_move.other_player();
-    Ok(())
+    Ok(delta)
   }
 }
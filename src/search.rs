@@ -1,37 +1,85 @@
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
+#[cfg(feature = "nnue")]
+use crate::nnue::{Accumulator, Network};
 use crate::rng::Rng;
-use crate::rules::{Color, Move, State};
+use crate::rules::{Color, Move, MoveDelta, State, ALL_CELLS_MASK};
+use crate::skill;
+use crate::timeman::TimeManager;
 
-struct FixedHashTable<const SIZE: usize, T> {
-  table: Vec<(u64, T)>,
+/// Size of the move ordering table, in megabytes, unless the Hash option says otherwise.
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// The move ordering table. It's shared between search threads, so entries are written without
+/// locks. Each entry stores the key XORed with the data, so a torn write just looks like a miss.
+struct MoveTable {
+  entries: Vec<(AtomicU64, AtomicU64)>,
 }
 
-impl<const SIZE: usize, T> FixedHashTable<SIZE, T> {
-  pub fn new() -> FixedHashTable<SIZE, T> {
-    let mut table = Vec::with_capacity(SIZE);
-    for _ in 0..SIZE {
-      table.push((0, unsafe { std::mem::zeroed() }));
+impl MoveTable {
+  fn new(size_mb: usize) -> MoveTable {
+    let count = (size_mb << 20) / std::mem::size_of::<(AtomicU64, AtomicU64)>();
+    MoveTable {
+      entries: (0..count.max(1)).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect(),
     }
-    FixedHashTable { table }
   }
 
-  fn get(&self, key: u64) -> Option<&T> {
-    let index = (key % SIZE as u64) as usize;
-    match self.table[index].0 == key {
-      true => Some(&self.table[index].1),
+  fn get(&self, key: u64) -> Option<Move> {
+    let entry = &self.entries[(key % self.entries.len() as u64) as usize];
+    let data = entry.1.load(Ordering::Relaxed);
+    match entry.0.load(Ordering::Relaxed) ^ data == key {
+      true => Some(Move::unpack(data as u16)),
       false => None,
     }
   }
 
-  fn set(&mut self, key: u64, value: T) {
-    let index = (key % SIZE as u64) as usize;
-    self.table[index] = (key, value);
+  fn set(&self, key: u64, m: Move) {
+    let entry = &self.entries[(key % self.entries.len() as u64) as usize];
+    let data = m.pack() as u64;
+    entry.0.store(key ^ data, Ordering::Relaxed);
+    entry.1.store(data, Ordering::Relaxed);
+  }
+
+  fn clear(&self) {
+    for entry in &self.entries {
+      entry.0.store(0, Ordering::Relaxed);
+      entry.1.store(0, Ordering::Relaxed);
+    }
   }
 }
 
-type Evaluation = i32;
+pub type Evaluation = i32;
+
+/// How often `pvs` looks at the clock, in nodes.
+const DEADLINE_CHECK_INTERVAL: u64 = 1024;
+
+/// The deepest iteration we run. Killer moves are indexed by depth, so this is bounded by them.
+const MAX_DEPTH: u16 = 63;
+
+/// Limits for a single search. Limits that are None don't constrain the search, and the search
+/// stops as soon as any limit is hit.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+  pub depth:       Option<u16>,
+  pub nodes:       Option<u64>,
+  pub movetime_ms: Option<i32>,
+  /// Time left on our clock and our increment, in milliseconds, for managed time control.
+  pub clock:       Option<(i32, i32)>,
+  /// Moves until the clock gets more time, if the time control has periods.
+  pub moves_to_go: Option<u32>,
+}
+
+/// One line of a MultiPV search, best line first.
+#[derive(Clone, Debug)]
+pub struct SearchLine {
+  pub depth: u16,
+  pub score: Evaluation,
+  /// Wins, draws and losses per thousand for the side to move, from searchers that count them.
+  pub wdl:   Option<[u32; 3]>,
+  pub pv:    Vec<Move>,
+}
 
 const VERY_NEGATIVE_EVAL: Evaluation = -1_000_000_000;
 const VERY_POSITIVE_EVAL: Evaluation = 1_000_000_000;
@@ -46,14 +94,113 @@ fn make_terminal_score_slightly_less_extreme(score: Evaluation) -> Evaluation {
   }
 }
 
+const PST_CLASSES: usize = 10;
+
+/// Number of weights in the linear evaluation.
+pub const EVAL_FEATURE_COUNT: usize = PST_CLASSES + 2;
+
+/// Names of the evaluation weights, as they appear in weight files.
+pub const EVAL_FEATURE_NAMES: [&str; EVAL_FEATURE_COUNT] = [
+  "pst_a1", "pst_b1", "pst_c1", "pst_d1", "pst_b2", "pst_c2", "pst_d2", "pst_c3", "pst_d3",
+  "pst_d4", "exposed", "tempo",
+];
+
+/// Maps each cell to its piece-square class. The board has the full dihedral symmetry, so we
+/// fold every cell into the triangle a1-d1-d4 and number the cells in there.
+fn pst_class(cell: u32) -> usize {
+  let fold = |c: u32| c.min(6 - c);
+  let (x, y) = (fold(cell % 8), fold(cell / 8));
+  let (lo, hi) = (x.min(y) as usize, x.max(y) as usize);
+  // Offsets of the rows of the triangle: lo=0 has 4 cells, lo=1 has 3, and so on.
+  [0, 4, 7, 9][lo] + hi - lo
+}
+
+/// Moore neighborhood dilation of a bitboard. The unused eighth column absorbs the wraparound.
+fn dilate(bitboard: u64) -> u64 {
+  let horizontal = bitboard | (bitboard << 1) | (bitboard >> 1);
+  (horizontal | (horizontal << 8) | (horizontal >> 8)) & ALL_CELLS_MASK
+}
+
+/// Computes the evaluation features of a position, each as black minus white.
+pub fn eval_features(state: &State) -> [i32; EVAL_FEATURE_COUNT] {
+  let mut features = [0; EVAL_FEATURE_COUNT];
+  let empty = !(state.black_stones | state.white_stones | state.gaps) & ALL_CELLS_MASK;
+  for (stones, sign) in [(state.black_stones, 1), (state.white_stones, -1)] {
+    let mut remaining = stones;
+    while remaining != 0 {
+      features[pst_class(remaining.trailing_zeros())] += sign;
+      remaining &= remaining - 1;
+    }
+    features[PST_CLASSES] += sign * (stones & dilate(empty)).count_ones() as i32;
+  }
+  features[PST_CLASSES + 1] = match state.to_move {
+    Color::Black => 1,
+    Color::White => -1,
+  };
+  features
+}
+
+/// Weights of the linear evaluation function, in centistones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+  pub weights: [i32; EVAL_FEATURE_COUNT],
+}
+
+impl Default for EvalParams {
+  fn default() -> EvalParams {
+    let mut weights = [0; EVAL_FEATURE_COUNT];
+    weights[..PST_CLASSES].fill(100);
+    EvalParams { weights }
+  }
+}
+
+impl EvalParams {
+  /// Parses a weight file: one `name value` pair per line, with `#` comments.
+  /// Weights that aren't mentioned keep their default values.
+  pub fn parse(text: &str) -> Result<EvalParams, String> {
+    let mut params = EvalParams::default();
+    for line in text.lines() {
+      let line = line.split('#').next().unwrap().trim();
+      if line.is_empty() {
+        continue;
+      }
+      let (name, value) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+        [name, value] => (name, value),
+        _ => return Err(format!("Malformed weight line: {}", line)),
+      };
+      let index = EVAL_FEATURE_NAMES
+        .iter()
+        .position(|n| *n == name)
+        .ok_or_else(|| format!("Unknown weight: {}", name))?;
+      params.weights[index] =
+        value.parse().map_err(|_| format!("Invalid weight value: {}", value))?;
+    }
+    Ok(params)
+  }
+
+  pub fn load(path: &str) -> Result<EvalParams, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    EvalParams::parse(&text)
+  }
+
+  pub fn to_file_string(&self) -> String {
+    let mut s = String::new();
+    for (name, weight) in EVAL_FEATURE_NAMES.iter().zip(self.weights) {
+      s.push_str(&format!("{} {}\n", name, weight));
+    }
+    s
+  }
+}
+
 /// Returns an evaluation for the current player.
-pub fn evaluate(state: &State) -> Evaluation {
-  let mut score =
-    100 * (state.black_stones.count_ones() as i32 - state.white_stones.count_ones() as i32);
+pub fn evaluate(params: &EvalParams, state: &State) -> Evaluation {
+  let features = eval_features(state);
+  let mut score: Evaluation = features.iter().zip(params.weights).map(|(f, w)| f * w).sum();
   if state.game_is_over() {
-    if score > 0 {
+    let material = state.black_stones.count_ones() as i32 - state.white_stones.count_ones() as i32;
+    if material > 0 {
       score += 1_000_000;
-    } else if score < 0 {
+    } else if material < 0 {
       score -= 1_000_000;
     }
   }
@@ -63,27 +210,272 @@ pub fn evaluate(state: &State) -> Evaluation {
   }
 }
 
+/// Stops a search from another thread. Clones share the same flag, so a GUI can keep one while
+/// the search runs elsewhere.
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<AtomicBool>);
+
+impl StopHandle {
+  pub fn new() -> StopHandle {
+    StopHandle::default()
+  }
+
+  /// Asks the search in progress to return its best move as soon as possible.
+  pub fn stop(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_stopped(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+
+  /// Searches call this when they start, so an old stop doesn't cut them short.
+  pub fn reset(&self) {
+    self.0.store(false, Ordering::Relaxed);
+  }
+}
+
+/// Reported after each completed iteration.
+#[derive(Debug, Clone)]
+pub struct IterationInfo {
+  pub depth:   u16,
+  pub nodes:   u64,
+  pub time_ms: u64,
+  /// Best first. There's more than one with MultiPV.
+  pub lines:   Vec<SearchLine>,
+}
+
+/// Reported once when a search returns.
+#[derive(Debug, Clone)]
+pub struct SearchStats {
+  /// The deepest completed iteration.
+  pub depth:     u16,
+  /// Nodes searched by all threads.
+  pub nodes:     u64,
+  pub time_ms:   u64,
+  pub score:     Evaluation,
+  pub best_move: Option<Move>,
+}
+
+impl SearchStats {
+  pub fn nodes_per_second(&self) -> u64 {
+    self.nodes * 1000 / self.time_ms.max(1)
+  }
+}
+
+/// Watches a search in progress. Every hook does nothing by default.
+pub trait SearchObserver: Send {
+  fn iteration_completed(&mut self, _info: &IterationInfo) {}
+  /// The move the search prefers changed.
+  fn best_move_changed(&mut self, _depth: u16, _score: Evaluation, _m: Move) {}
+  fn search_finished(&mut self, _stats: &SearchStats) {}
+  /// Diagnostic chatter, like time management decisions.
+  fn debug(&mut self, _message: &str) {}
+}
+
+/// The observer hooks as one value, for observers that just forward everything somewhere.
+#[derive(Debug, Clone)]
+pub enum SearchEvent {
+  IterationDone(IterationInfo),
+  BestMove {
+    depth: u16,
+    score: Evaluation,
+    m:     Move,
+  },
+  Finished(SearchStats),
+  Debug(String),
+}
+
+/// Any closure taking events is an observer.
+impl<F: FnMut(&SearchEvent) + Send> SearchObserver for F {
+  fn iteration_completed(&mut self, info: &IterationInfo) {
+    self(&SearchEvent::IterationDone(info.clone()))
+  }
+
+  fn best_move_changed(&mut self, depth: u16, score: Evaluation, m: Move) {
+    self(&SearchEvent::BestMove { depth, score, m })
+  }
+
+  fn search_finished(&mut self, stats: &SearchStats) {
+    self(&SearchEvent::Finished(stats.clone()))
+  }
+
+  fn debug(&mut self, message: &str) {
+    self(&SearchEvent::Debug(message.to_string()))
+  }
+}
+
+/// Anything that can pick moves: the alpha-beta `Engine`, MCTS, and the baselines we calibrate
+/// against. Searchers track their own position.
+pub trait Searcher: Send {
+  /// A short name, used to pick the searcher by option.
+  fn name(&self) -> &'static str;
+  fn state(&self) -> &State;
+  fn set_position(&mut self, state: State);
+  fn make_move(&mut self, m: Move) -> Result<(), &'static str>;
+  fn search(&mut self, limits: &SearchLimits) -> (Evaluation, Option<Move>);
+  fn stop_handle(&self) -> StopHandle;
+  /// Whether the searcher reports its lines to a `SearchObserver`, so callers don't need to.
+  fn reports_progress(&self) -> bool {
+    false
+  }
+}
+
 pub struct Engine {
-  rng:              Rng,
-  pub state:        State,
-  move_order_table: FixedHashTable<{ 1 << 20 }, Move>,
-  killer_moves:     [Option<Move>; 64],
-  do_stop:          Arc<AtomicBool>,
-  nodes:            u64,
+  rng:                  Rng,
+  seed:                 u64,
+  pub state:            State,
+  pub eval_params:      EvalParams,
+  move_order_table:     Arc<MoveTable>,
+  killer_moves:         [Option<Move>; 64],
+  do_stop:              StopHandle,
+  nodes:                u64,
+  node_limit:           u64,
+  /// Search threads, including this one. Helpers share the move ordering table (lazy SMP).
+  pub threads:          usize,
+  /// How many best lines to search at the root.
+  pub multi_pv:         usize,
+  /// Milliseconds subtracted from every time limit, to cover communication delays.
+  pub move_overhead_ms: i32,
+  /// Leaf evaluations get a random bonus in [0, noise). Zero disables it.
+  pub noise:            u32,
+  /// From 0 to `skill::MAX_SKILL_LEVEL`, which is full strength.
+  pub skill_level:      u8,
+  /// How many lines the root searches. Below full strength that's more than `multi_pv`, to give
+  /// the skill level some candidates to pick from.
+  root_lines:           usize,
+  /// While searching extra MultiPV lines, the root skips the moves of the lines already found.
+  root_depth:           u16,
+  excluded_root_moves:  Vec<Move>,
+  /// Root moves whose subtree was searched to the end in the current iteration.
+  completed_root_moves: Vec<Move>,
+  /// Set when a stop cut the current iteration short, so its results are incomplete.
+  aborted:              bool,
+  lines:                Vec<SearchLine>,
+  search_start:         Instant,
+  time_manager:         Option<TimeManager>,
+  /// The time manager's hard deadline, kept here to be cheap to check in `pvs`.
+  hard_deadline:        Option<Instant>,
+  observer:             Option<Box<dyn SearchObserver>>,
+  #[cfg(feature = "nnue")]
+  network:              Option<Arc<Network>>,
+  /// Accumulators for the positions on the current search path, root first.
+  #[cfg(feature = "nnue")]
+  accumulators:         Vec<Accumulator>,
 }
 
 impl Engine {
   pub fn new(seed: u64) -> Engine {
     Engine {
-      nodes:            0,
-      rng:              Rng::new(seed),
-      state:            State::new(),
-      move_order_table: FixedHashTable::new(),
-      killer_moves:     [None; 64],
-      do_stop:          Arc::new(AtomicBool::new(false)),
+      rng: Rng::new(seed),
+      seed,
+      state: State::new(),
+      eval_params: EvalParams::default(),
+      move_order_table: Arc::new(MoveTable::new(DEFAULT_HASH_MB)),
+      killer_moves: [None; 64],
+      do_stop: StopHandle::new(),
+      nodes: 0,
+      node_limit: u64::MAX,
+      threads: 1,
+      multi_pv: 1,
+      move_overhead_ms: 0,
+      noise: 15,
+      skill_level: skill::MAX_SKILL_LEVEL,
+      root_lines: 1,
+      root_depth: 0,
+      excluded_root_moves: Vec::new(),
+      completed_root_moves: Vec::new(),
+      aborted: false,
+      lines: Vec::new(),
+      search_start: Instant::now(),
+      time_manager: None,
+      hard_deadline: None,
+      observer: None,
+      #[cfg(feature = "nnue")]
+      network: None,
+      #[cfg(feature = "nnue")]
+      accumulators: Vec::new(),
     }
   }
 
+  /// A helper thread's engine, which shares our table and stop flag.
+  fn helper(&self) -> Engine {
+    Engine {
+      rng:                                   Rng::new(self.rng.next_random()),
+      seed:                                  self.seed,
+      state:                                 self.state.clone(),
+      eval_params:                           self.eval_params.clone(),
+      move_order_table:                      self.move_order_table.clone(),
+      killer_moves:                          [None; 64],
+      do_stop:                               self.do_stop.clone(),
+      nodes:                                 0,
+      node_limit:                            u64::MAX,
+      threads:                               1,
+      multi_pv:                              1,
+      move_overhead_ms:                      self.move_overhead_ms,
+      noise:                                 self.noise,
+      skill_level:                           self.skill_level,
+      root_lines:                            1,
+      root_depth:                            0,
+      excluded_root_moves:                   Vec::new(),
+      completed_root_moves:                  Vec::new(),
+      aborted:                               false,
+      lines:                                 Vec::new(),
+      search_start:                          Instant::now(),
+      time_manager:                          None,
+      hard_deadline:                         None,
+      observer:                              None,
+      #[cfg(feature = "nnue")]
+      network:                               self.network.clone(),
+      #[cfg(feature = "nnue")]
+      accumulators:                          Vec::new(),
+    }
+  }
+
+  /// Reports the progress of the following searches to `observer`, or to nobody with None.
+  pub fn set_observer(&mut self, observer: Option<Box<dyn SearchObserver>>) {
+    self.observer = observer;
+  }
+
+  /// Stops the search in progress, from any thread.
+  pub fn stop_handle(&self) -> StopHandle {
+    self.do_stop.clone()
+  }
+
+  /// Reallocates the move ordering table, which also clears it.
+  pub fn set_hash_size_mb(&mut self, size_mb: usize) {
+    self.move_order_table = Arc::new(MoveTable::new(size_mb));
+  }
+
+  pub fn set_seed(&mut self, seed: u64) {
+    self.rng = Rng::new(seed);
+    self.seed = seed;
+  }
+
+  /// The seed of the evaluation noise. With one thread, the same seed and position searched to the
+  /// same depth always give the same result.
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+
+  /// Forgets everything learned from previous searches, for a new game.
+  pub fn clear(&mut self) {
+    self.move_order_table.clear();
+    self.killer_moves = [None; 64];
+    // Restart the noise too, so that games are reproducible from their seed.
+    self.rng = Rng::new(self.seed);
+  }
+
+  /// Nodes searched by this thread in the last search.
+  pub fn nodes(&self) -> u64 {
+    self.nodes
+  }
+
+  /// The lines of the last search, the one played first. There's more than one with MultiPV.
+  pub fn lines(&self) -> &[SearchLine] {
+    &self.lines
+  }
+
   pub fn make_move(&mut self, m: Move) -> Result<(), &'static str> {
     self.state.make_move(m)
   }
@@ -92,45 +484,302 @@ impl Engine {
     self.state = state;
   }
 
-  pub fn run_depth(&mut self, max_depth: u16) -> (Evaluation, Option<Move>) {
+  /// Switches the leaf evaluation to a network, or back to `evaluate` with None.
+  #[cfg(feature = "nnue")]
+  pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+    self.network = network;
+  }
+
+  fn prepare_search(&mut self, state: &State) {
     self.nodes = 0;
-    let mut p = (0, None);
-    let state = self.state.clone();
-    // Iterative deepening.
-    for d in 1..=max_depth {
-      p = self.pvs(d, &state, VERY_NEGATIVE_EVAL, VERY_POSITIVE_EVAL);
+    self.aborted = false;
+    #[cfg(feature = "nnue")]
+    {
+      self.accumulators.clear();
+      if let Some(network) = &self.network {
+        self.accumulators.push(Accumulator::refresh(network, state));
+      }
     }
-    p
+    #[cfg(not(feature = "nnue"))]
+    let _ = state;
   }
 
-  pub fn run_time(&mut self, movetime_ms: i32) -> (Evaluation, Option<Move>) {
-    self.nodes = 0;
-    let mut p;
+  #[cfg(feature = "nnue")]
+  fn push_accumulator(&mut self, delta: &MoveDelta) {
+    if let Some(network) = &self.network {
+      let mut accumulator = self.accumulators.last().unwrap().clone();
+      accumulator.update(network, delta);
+      self.accumulators.push(accumulator);
+    }
+  }
+
+  #[cfg(feature = "nnue")]
+  fn pop_accumulator(&mut self) {
+    self.accumulators.pop();
+  }
+
+  #[cfg(not(feature = "nnue"))]
+  fn push_accumulator(&mut self, _delta: &MoveDelta) {}
+
+  #[cfg(not(feature = "nnue"))]
+  fn pop_accumulator(&mut self) {}
+
+  fn leaf_eval(&self, state: &State) -> Evaluation {
+    #[cfg(feature = "nnue")]
+    if let (Some(network), false) = (&self.network, state.game_is_over()) {
+      return network.evaluate(self.accumulators.last().unwrap(), state.to_move);
+    }
+    evaluate(&self.eval_params, state)
+  }
+
+  pub fn run(&mut self, limits: &SearchLimits) -> (Evaluation, Option<Move>) {
+    self.search_start = Instant::now();
     let state = self.state.clone();
-    self.do_stop.store(false, std::sync::atomic::Ordering::Relaxed);
-    // Use a thread to stop the search after the given time.
-    let do_stop = self.do_stop.clone();
-    std::thread::spawn(move || {
-      std::thread::sleep(std::time::Duration::from_millis(movetime_ms as u64));
-      do_stop.store(true, std::sync::atomic::Ordering::Relaxed);
+    let skill_nodes = skill::node_limit(self.skill_level).unwrap_or(u64::MAX);
+    self.node_limit = limits.nodes.unwrap_or(u64::MAX).min(skill_nodes);
+    self.do_stop.reset();
+    self.time_manager = match (limits.movetime_ms, limits.clock) {
+      (Some(movetime_ms), _) => Some(TimeManager::fixed(movetime_ms, self.move_overhead_ms)),
+      (None, Some((ms_on_clock, ms_increment))) => Some(TimeManager::for_clock(
+        &self.state,
+        ms_on_clock,
+        ms_increment,
+        limits.moves_to_go,
+        self.move_overhead_ms,
+      )),
+      (None, None) => None,
+    };
+    if let (Some(time_manager), Some(observer)) = (&self.time_manager, &mut self.observer) {
+      observer.debug(&format!(
+        "time optimum {} ms maximum {} ms",
+        time_manager.optimum_ms(),
+        time_manager.maximum_ms()
+      ));
+    }
+    self.hard_deadline = self.time_manager.as_ref().map(|t| t.hard_deadline());
+    let max_depth = limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+    // Below full strength we want a few candidate moves to choose between.
+    self.root_lines = match self.skill_level < skill::MAX_SKILL_LEVEL {
+      true => self.multi_pv.max(skill::CANDIDATES),
+      false => self.multi_pv,
+    };
+    let mut helpers = (1..self.threads).map(|_| self.helper()).collect::<Vec<_>>();
+    std::thread::scope(|scope| {
+      for helper in &mut helpers {
+        let state = &state;
+        scope.spawn(move || helper.iterative_deepening(state, MAX_DEPTH));
+      }
+      self.iterative_deepening(&state, max_depth);
+      // The helpers keep going until the main thread is done.
+      self.do_stop.stop();
     });
-    let start = std::time::Instant::now();
-    // Iterative deepening.
+    self.node_limit = u64::MAX;
+    let pick = skill::pick_line(&self.lines, self.skill_level, &self.rng);
+    if pick != 0 {
+      let line = self.lines.remove(pick);
+      if let Some(observer) = &mut self.observer {
+        observer.debug(&format!(
+          "skill level {} plays line {} instead of the best",
+          self.skill_level,
+          pick + 1
+        ));
+      }
+      self.lines.insert(0, line);
+    }
+    if let Some(m) = skill::blunder(&state, self.skill_level, &self.rng) {
+      let line = match self.lines.iter().position(|line| line.pv[0] == m) {
+        Some(i) => self.lines.remove(i),
+        // We didn't search it, so score it by the classic evaluation after the move.
+        None => {
+          let mut after = state.clone();
+          after.make_move(m).unwrap();
+          SearchLine {
+            depth: 1,
+            score: -evaluate(&self.eval_params, &after),
+            wdl:   None,
+            pv:    vec![m],
+          }
+        }
+      };
+      if let Some(observer) = &mut self.observer {
+        observer.debug(&format!(
+          "skill level {} plays the random move {}",
+          self.skill_level,
+          m.to_uai()
+        ));
+      }
+      self.lines.insert(0, line);
+    }
+    self.lines.truncate(self.multi_pv);
+    let (score, best_move) = match self.lines.first() {
+      Some(line) => (line.score, line.pv.first().copied()),
+      None => (self.leaf_eval(&state), None),
+    };
+    if let Some(observer) = &mut self.observer {
+      observer.search_finished(&SearchStats {
+        depth: self.lines.first().map_or(0, |line| line.depth),
+        nodes: self.nodes + helpers.iter().map(|helper| helper.nodes).sum::<u64>(),
+        time_ms: self.search_start.elapsed().as_millis() as u64,
+        score,
+        best_move,
+      });
+    }
+    (score, best_move)
+  }
+
+  /// Searches deeper and deeper until we hit `max_depth` or are told to stop.
+  fn iterative_deepening(&mut self, state: &State, max_depth: u16) {
+    self.prepare_search(state);
+    self.lines.clear();
     let mut depth = 0;
-    loop {
+    while depth < max_depth {
       depth += 1;
-      p = self.pvs(depth, &state, VERY_NEGATIVE_EVAL, VERY_POSITIVE_EVAL);
-      if self.do_stop.load(std::sync::atomic::Ordering::Relaxed) {
+      let previous_best = self.lines.first().map(|line| line.pv[0]);
+      let lines = self.search_lines(depth, state);
+      if self.aborted {
+        self.accept_partial_iteration(depth, lines);
+        break;
+      }
+      self.lines = lines;
+      if let Some(observer) = &mut self.observer {
+        if let Some(line) = self.lines.first() {
+          if previous_best != Some(line.pv[0]) {
+            observer.best_move_changed(depth, line.score, line.pv[0]);
+          }
+        }
+        observer.iteration_completed(&IterationInfo {
+          depth,
+          nodes: self.nodes,
+          time_ms: self.search_start.elapsed().as_millis() as u64,
+          lines: self.lines.iter().take(self.multi_pv).cloned().collect(),
+        });
+      }
+      if self.should_stop() {
         break;
       }
+      if let (Some(time_manager), Some(line)) = (&mut self.time_manager, self.lines.first()) {
+        time_manager.update(line.pv[0], line.score);
+        if !time_manager.should_start_iteration() {
+          break;
+        }
+      }
     }
-    println!(
-      "info time {} nodes {} depth {}",
-      start.elapsed().as_millis(),
-      self.nodes,
-      depth,
-    );
-    p
+  }
+
+  /// Takes the best line from an iteration that was cut short, but only if its move is proven at
+  /// least as good as the last completed iteration's: either it's the same move, or that move was
+  /// searched to the end and lost to it.
+  fn accept_partial_iteration(&mut self, depth: u16, partial: Vec<SearchLine>) {
+    if let Some(line) = partial.into_iter().next() {
+      let accept = match self.lines.first() {
+        None => true,
+        Some(previous) => {
+          previous.pv[0] == line.pv[0] || self.completed_root_moves.contains(&previous.pv[0])
+        }
+      };
+      if accept {
+        self.lines.retain(|l| l.pv[0] != line.pv[0]);
+        self.lines.insert(0, line);
+      }
+    }
+    if let (Some(observer), Some(line)) = (&mut self.observer, self.lines.first()) {
+      observer.debug(&format!(
+        "depth {} was interrupted, answering from depth {}",
+        depth, line.depth
+      ));
+    }
+  }
+
+  /// Whether we're out of time or nodes. Depth 1 always finishes, so there's a move to play.
+  fn should_stop(&self) -> bool {
+    self.root_depth > 1 && (self.do_stop.is_stopped() || self.nodes >= self.node_limit)
+  }
+
+  /// Stops the search, helpers included, once we're past the hard deadline.
+  fn check_deadline(&self) {
+    if self.hard_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+      self.do_stop.stop();
+    }
+  }
+
+  /// Searches the root once per MultiPV line, excluding the moves of the lines already found.
+  fn search_lines(&mut self, depth: u16, state: &State) -> Vec<SearchLine> {
+    let mut root_moves = Vec::new();
+    state.move_gen(&mut root_moves);
+    self.root_depth = depth;
+    self.excluded_root_moves.clear();
+    self.completed_root_moves.clear();
+    let mut lines = Vec::new();
+    while lines.len() < self.root_lines.min(root_moves.len()) {
+      let (score, m) = self.pvs(depth, state, VERY_NEGATIVE_EVAL, VERY_POSITIVE_EVAL);
+      let m = match m {
+        Some(m) => m,
+        None => break,
+      };
+      let pv = self.extract_pv(state, m, depth);
+      lines.push(SearchLine {
+        depth,
+        score,
+        wdl: None,
+        pv,
+      });
+      self.excluded_root_moves.push(m);
+      if self.should_stop() {
+        self.aborted |= lines.len() < self.root_lines.min(root_moves.len());
+        break;
+      }
+    }
+    self.excluded_root_moves.clear();
+    // A later line can come back with a better score than the ones before it. A cut-short
+    // iteration keeps its order, since only its first line searched every move.
+    if !self.aborted {
+      lines.sort_by_key(|line| std::cmp::Reverse(line.score));
+    }
+    // The extra lines overwrote the root's entry, so put the best move back for the next depth.
+    if let Some(line) = lines.first() {
+      self.move_order_table.set(state.get_hash(), line.pv[0]);
+    }
+    lines
+  }
+
+  /// Follows the move ordering table from the position after `first` to build a PV.
+  fn extract_pv(&self, state: &State, first: Move, max_length: u16) -> Vec<Move> {
+    let mut pv = vec![first];
+    let mut state = state.clone();
+    state.make_move(first).unwrap();
+    while pv.len() < max_length as usize && !state.game_is_over() {
+      match self.move_order_table.get(state.get_hash()) {
+        Some(m) if state.is_legal(m) => {
+          pv.push(m);
+          state.make_move(m).unwrap();
+        }
+        _ => break,
+      }
+    }
+    pv
+  }
+
+  pub fn run_depth(&mut self, max_depth: u16) -> (Evaluation, Option<Move>) {
+    self.run(&SearchLimits {
+      depth: Some(max_depth),
+      ..SearchLimits::default()
+    })
+  }
+
+  /// Searches until roughly `max_nodes` nodes have been visited. Unlike the timed searches this
+  /// is reproducible, which makes it the right limit for data generation.
+  pub fn run_nodes(&mut self, max_nodes: u64) -> (Evaluation, Option<Move>) {
+    self.run(&SearchLimits {
+      nodes: Some(max_nodes),
+      ..SearchLimits::default()
+    })
+  }
+
+  pub fn run_time(&mut self, movetime_ms: i32) -> (Evaluation, Option<Move>) {
+    self.run(&SearchLimits {
+      movetime_ms: Some(movetime_ms),
+      ..SearchLimits::default()
+    })
   }
 
   pub fn run_time_managed(
@@ -138,22 +787,10 @@ impl Engine {
     ms_on_clock: i32,
     ms_increment: i32,
   ) -> (Evaluation, Option<Move>) {
-    // Estimate the number of moves left in the game.
-    let filled = (self.state.black_stones | self.state.white_stones | self.state.gaps).count_ones();
-    let moves_left = 1 + (7 * 7 - filled) * 2;
-    assert!(moves_left > 0);
-    // Estimate the time we have left.
-    let time_left = ms_on_clock + ms_increment * moves_left as i32;
-    // Estimate the time we should spend on this move.
-    let time_per_move = (time_left as f32 / moves_left as f32) as i32;
-    // Make sure we don't spend more than half of our time on this move.
-    let time_to_spend = time_per_move.min(ms_on_clock / 2);
-    println!(
-      "moves_left: {moves_left} time_left: {}, time_per_move: {}, time_to_spend: {}",
-      time_left, time_per_move, time_to_spend
-    );
-    // Run the search.
-    self.run_time(time_to_spend)
+    self.run(&SearchLimits {
+      clock: Some((ms_on_clock, ms_increment)),
+      ..SearchLimits::default()
+    })
   }
 
   pub fn pvs(
@@ -163,20 +800,26 @@ impl Engine {
     mut alpha: Evaluation,
     beta: Evaluation,
   ) -> (Evaluation, Option<Move>) {
-    let random_bonus = || self.rng.generate_range(15) as i32;
+    let random_bonus = || match self.noise {
+      0 => 0,
+      noise => self.rng.generate_range(noise) as i32,
+    };
     if state.game_is_over() || depth == 0 {
-      return (evaluate(state) + random_bonus(), None);
+      return (self.leaf_eval(state) + random_bonus(), None);
     }
 
     let mut moves = Vec::new();
     state.move_gen(&mut moves);
+    if depth == self.root_depth {
+      moves.retain(|m| !self.excluded_root_moves.contains(m));
+    }
     if moves.is_empty() {
-      return (evaluate(state) + random_bonus(), None);
+      return (self.leaf_eval(state) + random_bonus(), None);
     }
 
     // Sort moves by score.
     let state_hash = state.get_hash();
-    let mot_move = self.move_order_table.get(state_hash).copied();
+    let mot_move = self.move_order_table.get(state_hash);
     let killer_move = self.killer_moves[depth as usize];
     moves.sort_by_key(|m| match (mot_move, killer_move) {
       (Some(mot_move), _) if mot_move == *m => 2,
@@ -189,8 +832,12 @@ impl Engine {
     let mut best_move = None;
     for m in moves {
       let mut new_state = state.clone();
-      new_state.make_move(m).unwrap();
+      let delta = new_state.make_move_delta(m).unwrap();
+      self.push_accumulator(&delta);
       self.nodes += 1;
+      if self.nodes.is_multiple_of(DEADLINE_CHECK_INTERVAL) {
+        self.check_deadline();
+      }
       // Recurse on subtrees.
       let mut score;
       if first {
@@ -201,6 +848,14 @@ impl Engine {
           score = -self.pvs(depth - 1, &new_state, -beta, -score).0;
         }
       }
+      self.pop_accumulator();
+      // A subtree cut short by a stop has a meaningless score.
+      if self.aborted {
+        break;
+      }
+      if depth == self.root_depth {
+        self.completed_root_moves.push(m);
+      }
       // Evaluate cut-offs, etc.
       if score > best_score {
         best_score = score;
@@ -214,8 +869,9 @@ impl Engine {
         self.killer_moves[depth as usize] = Some(m);
         break;
       }
-      // If we're out of time then stop early.
-      if self.do_stop.load(std::sync::atomic::Ordering::Relaxed) {
+      // If we're out of time or nodes then stop early.
+      if self.should_stop() {
+        self.aborted = true;
         break;
       }
       first = false;
@@ -225,3 +881,33 @@ impl Engine {
     (make_terminal_score_slightly_less_extreme(alpha), best_move)
   }
 }
+
+impl Searcher for Engine {
+  fn name(&self) -> &'static str {
+    "ab"
+  }
+
+  fn state(&self) -> &State {
+    &self.state
+  }
+
+  fn set_position(&mut self, state: State) {
+    Engine::set_position(self, state)
+  }
+
+  fn make_move(&mut self, m: Move) -> Result<(), &'static str> {
+    Engine::make_move(self, m)
+  }
+
+  fn search(&mut self, limits: &SearchLimits) -> (Evaluation, Option<Move>) {
+    self.run(limits)
+  }
+
+  fn stop_handle(&self) -> StopHandle {
+    self.do_stop.clone()
+  }
+
+  fn reports_progress(&self) -> bool {
+    true
+  }
+}
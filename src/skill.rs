@@ -0,0 +1,87 @@
+//! Strength limiting, so the engine can be a sparring partner for people.
+//!
+//! Below full strength the engine searches less, and picks among its best few moves at random,
+//! preferring the better ones more strongly at higher levels. Even a one-ply search beats random
+//! play nearly every time, so the lowest levels also play a random legal move some of the time.
+
+use crate::rng::Rng;
+use crate::rules::{Move, State};
+use crate::search::SearchLine;
+
+pub const MAX_SKILL_LEVEL: u8 = 20;
+
+/// How many moves a weakened engine chooses between.
+pub const CANDIDATES: usize = 4;
+
+/// Approximate Elo of each skill level, with `RandomMover` at 0, as measured by `skill-calibrate`
+/// with its defaults: 200 games against the level below, or `RandomMover` for level 0, and 1M
+/// nodes per move at full strength. Every level won more than the level below with 95%
+/// confidence, by 56 Elo at the least, and each step is good to about 50 Elo either way.
+pub const SKILL_ELO: [i32; MAX_SKILL_LEVEL as usize + 1] = [
+  258, 397, 512, 635, 754, 835, 928, 1047, 1121, 1429, 1693, 1861, 1942, 2016, 2128, 2209, 2272,
+  2328, 2410, 2506, 2647,
+];
+
+/// The highest level whose Elo is at most `elo`, and at least level 0.
+pub fn skill_for_elo(elo: i32) -> u8 {
+  SKILL_ELO.iter().rposition(|e| *e <= elo).unwrap_or(0) as u8
+}
+
+/// Nodes each level may search. The bottom levels only get through one ply, which always
+/// finishes. Past a few thousand nodes more search gains little, so the top levels grow faster.
+const NODE_LIMITS: [u64; MAX_SKILL_LEVEL as usize] = [
+  1, 1, 1, 1, 1, 1, 1, 1, 1, 700, 1200, 2000, 5000, 9000, 15_000, 25_000, 55_000, 90_000, 180_000,
+  400_000,
+];
+
+/// How often each level plays a random legal move instead of the one it picked, in percent.
+const BLUNDER_PERCENT: [u32; MAX_SKILL_LEVEL as usize] = [
+  80, 68, 56, 45, 35, 26, 18, 10, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+pub fn node_limit(level: u8) -> Option<u64> {
+  NODE_LIMITS.get(level as usize).copied()
+}
+
+/// A random legal move to play instead of the searched ones, or None.
+pub fn blunder(state: &State, level: u8, rng: &Rng) -> Option<Move> {
+  match BLUNDER_PERCENT.get(level as usize) {
+    Some(&percent) if rng.generate_range(100) < percent => {}
+    _ => return None,
+  }
+  let mut moves = Vec::new();
+  state.move_gen(&mut moves);
+  match moves.is_empty() {
+    true => None,
+    false => Some(moves[rng.generate_range(moves.len() as u32) as usize]),
+  }
+}
+
+/// Picks one of the lines, with a softmax over their scores. The temperature, in centistones,
+/// falls as the level rises, so strong levels almost always play the best move.
+pub fn pick_line(lines: &[SearchLine], level: u8, rng: &Rng) -> usize {
+  // When a search stops partway through an iteration, the best line can be a ply deeper than the
+  // rest, and scores swing between odd and even depths, so only lines as deep as it compete.
+  let lines = match lines.first() {
+    Some(first) => &lines[..lines.iter().take_while(|line| line.depth == first.depth).count()],
+    None => lines,
+  };
+  if level >= MAX_SKILL_LEVEL || lines.len() < 2 {
+    return 0;
+  }
+  let temperature = 1.0 + 6.0 * (MAX_SKILL_LEVEL - 1 - level) as f64;
+  let best = lines.iter().map(|line| line.score).max().unwrap();
+  let weights = lines
+    .iter()
+    .map(|line| ((line.score - best) as f64 / temperature).exp())
+    .collect::<Vec<_>>();
+  let total = weights.iter().sum::<f64>();
+  let mut target = rng.generate_range(1 << 30) as f64 / (1u32 << 30) as f64 * total;
+  for (i, weight) in weights.iter().enumerate() {
+    if target < *weight {
+      return i;
+    }
+    target -= weight;
+  }
+  lines.len() - 1
+}
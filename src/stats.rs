@@ -0,0 +1,238 @@
+//! Statistics for engine testing: Elo estimates with error bars, and the sequential probability
+//! ratio test (SPRT) that tells when a match has played enough games.
+//!
+//! Elo is on the logistic scale, where a 400 point advantage means 10 to 1 odds. The SPRT is the
+//! generalized one fishtest uses, which compares the most likely outcome distributions with the
+//! expected scores of each hypothesis.
+
+/// The normal distribution's 97.5th percentile, for 95% error bars.
+const Z_95: f64 = 1.959964;
+
+/// Stands in for outcomes that haven't happened yet, so a handful of games with no variance don't
+/// give infinite confidence.
+const PSEUDO_COUNT: f64 = 1e-3;
+
+/// Estimates stop this far from zero. A perfect score has no finite Elo, and a gap this big says
+/// all there is to say.
+pub const MAX_ELO: f64 = 2000.0;
+
+pub fn elo_to_score(elo: f64) -> f64 {
+  1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// The Elo difference that gives this expected score. Scores of 0 and 1 give infinities.
+pub fn score_to_elo(score: f64) -> f64 {
+  -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// An Elo difference and its 95% confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EloEstimate {
+  pub elo:   f64,
+  pub lower: f64,
+  pub upper: f64,
+}
+
+impl EloEstimate {
+  /// Half the width of the interval, for printing as `elo +/- error`.
+  pub fn error(&self) -> f64 {
+    (self.upper - self.lower) / 2.0
+  }
+}
+
+/// The observed outcomes as (score, frequency) pairs, and the number of samples. Outcomes that
+/// haven't happened yet get a tiny frequency, so that a few games can't rule them out entirely.
+fn distribution(outcomes: &[(f64, u32)]) -> (Vec<(f64, f64)>, f64) {
+  let n = outcomes.iter().map(|(_, count)| *count as f64).sum::<f64>();
+  if n == 0.0 {
+    return (Vec::new(), 0.0);
+  }
+  let counts = outcomes.iter().map(|(score, count)| (*score, (*count as f64).max(PSEUDO_COUNT)));
+  let total = counts.clone().map(|(_, count)| count).sum::<f64>();
+  (
+    counts.map(|(score, count)| (score, count / total)).collect(),
+    n,
+  )
+}
+
+fn mean_and_variance(frequencies: &[(f64, f64)]) -> (f64, f64) {
+  let mean = frequencies.iter().map(|(score, p)| score * p).sum::<f64>();
+  let variance = frequencies.iter().map(|(score, p)| p * (score - mean).powi(2)).sum::<f64>();
+  (mean, variance)
+}
+
+fn estimate(outcomes: &[(f64, u32)]) -> EloEstimate {
+  let (frequencies, n) = distribution(outcomes);
+  if n == 0.0 {
+    return EloEstimate {
+      elo:   0.0,
+      lower: f64::NEG_INFINITY,
+      upper: f64::INFINITY,
+    };
+  }
+  let (mean, variance) = mean_and_variance(&frequencies);
+  let error = Z_95 * (variance / n).sqrt();
+  let elo = |score: f64| score_to_elo(score.clamp(0.0, 1.0)).clamp(-MAX_ELO, MAX_ELO);
+  EloEstimate {
+    elo:   elo(mean),
+    lower: elo(mean - error),
+    upper: elo(mean + error),
+  }
+}
+
+/// The distribution closest to the observed one, in the maximum likelihood sense, whose expected
+/// score is `score`. It has the form p_i / (1 + theta (a_i - score)), and we find theta by
+/// bisection.
+fn closest_with_score(frequencies: &[(f64, f64)], score: f64) -> Vec<f64> {
+  let (min, max) = frequencies.iter().fold((f64::MAX, f64::MIN), |(min, max), (a, _)| {
+    (min.min(*a), max.max(*a))
+  });
+  // Theta has to keep every denominator positive.
+  let (mut low, mut high) = (-1.0 / (max - score), 1.0 / (score - min));
+  let shifted_mean = |theta: f64| {
+    frequencies
+      .iter()
+      .map(|(a, p)| p * (a - score) / (1.0 + theta * (a - score)))
+      .sum::<f64>()
+  };
+  for _ in 0..100 {
+    let mid = (low + high) / 2.0;
+    match shifted_mean(mid) > 0.0 {
+      true => low = mid,
+      false => high = mid,
+    }
+  }
+  let theta = (low + high) / 2.0;
+  frequencies.iter().map(|(a, p)| p / (1.0 + theta * (a - score))).collect()
+}
+
+/// The log-likelihood ratio of elo1 against elo0.
+fn llr(outcomes: &[(f64, u32)], sprt: &Sprt) -> f64 {
+  let (frequencies, n) = distribution(outcomes);
+  if n == 0.0 {
+    return 0.0;
+  }
+  let p0 = closest_with_score(&frequencies, elo_to_score(sprt.elo0));
+  let p1 = closest_with_score(&frequencies, elo_to_score(sprt.elo1));
+  let per_sample =
+    frequencies.iter().zip(p0.iter().zip(&p1)).map(|((_, p), (p0, p1))| p * (p1 / p0).ln());
+  n * per_sample.sum::<f64>()
+}
+
+/// Wins, losses and draws of one player, counted game by game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Trinomial {
+  pub wins:   u32,
+  pub losses: u32,
+  pub draws:  u32,
+}
+
+impl Trinomial {
+  pub fn games(&self) -> u32 {
+    self.wins + self.losses + self.draws
+  }
+
+  pub fn score(&self) -> f64 {
+    (self.wins as f64 + 0.5 * self.draws as f64) / self.games().max(1) as f64
+  }
+
+  fn outcomes(&self) -> [(f64, u32); 3] {
+    [(0.0, self.losses), (0.5, self.draws), (1.0, self.wins)]
+  }
+
+  pub fn elo(&self) -> EloEstimate {
+    estimate(&self.outcomes())
+  }
+
+  pub fn llr(&self, sprt: &Sprt) -> f64 {
+    llr(&self.outcomes(), sprt)
+  }
+}
+
+/// One player's results over pairs of games, where each pair plays an opening once with each
+/// colour. Pairs are counted by the points the player took from them, in half points from 0 to 4.
+///
+/// Openings with a big advantage for one side make the two games of a pair anti-correlated, so
+/// counting pairs gives tighter error bars than counting games.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Pentanomial {
+  pub counts: [u32; 5],
+}
+
+impl Pentanomial {
+  /// Records a pair of games, with each game's score for the player: 0, 0.5 or 1.
+  pub fn add_pair(&mut self, first: f64, second: f64) {
+    self.counts[((first + second) * 2.0).round() as usize] += 1;
+  }
+
+  pub fn pairs(&self) -> u32 {
+    self.counts.iter().sum()
+  }
+
+  fn outcomes(&self) -> [(f64, u32); 5] {
+    let mut outcomes = [(0.0, 0); 5];
+    for (i, count) in self.counts.iter().enumerate() {
+      outcomes[i] = (i as f64 / 4.0, *count);
+    }
+    outcomes
+  }
+
+  pub fn elo(&self) -> EloEstimate {
+    estimate(&self.outcomes())
+  }
+
+  pub fn llr(&self, sprt: &Sprt) -> f64 {
+    llr(&self.outcomes(), sprt)
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtResult {
+  Continue,
+  /// The player is no better than elo0.
+  AcceptH0,
+  /// The player is at least elo1 better.
+  AcceptH1,
+}
+
+/// A test of the hypothesis that the player is elo0 better, against it being elo1 better, with
+/// false positive rate alpha and false negative rate beta.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+  pub elo0:  f64,
+  pub elo1:  f64,
+  pub alpha: f64,
+  pub beta:  f64,
+}
+
+impl Default for Sprt {
+  fn default() -> Sprt {
+    Sprt {
+      elo0:  0.0,
+      elo1:  5.0,
+      alpha: 0.05,
+      beta:  0.05,
+    }
+  }
+}
+
+impl Sprt {
+  /// The LLRs at which the test accepts H0 and H1.
+  pub fn bounds(&self) -> (f64, f64) {
+    (
+      (self.beta / (1.0 - self.alpha)).ln(),
+      ((1.0 - self.beta) / self.alpha).ln(),
+    )
+  }
+
+  pub fn result(&self, llr: f64) -> SprtResult {
+    let (lower, upper) = self.bounds();
+    if llr <= lower {
+      SprtResult::AcceptH0
+    } else if llr >= upper {
+      SprtResult::AcceptH1
+    } else {
+      SprtResult::Continue
+    }
+  }
+}
@@ -0,0 +1,139 @@
+//! Decides how long a search may take.
+//!
+//! Under a clock we aim for an optimum time, which grows when the best move keeps changing or the
+//! score drops, and shrinks when the best move is stable. The maximum time is never exceeded.
+
+use std::time::{Duration, Instant};
+
+use crate::rules::{Move, State, ALL_CELLS_MASK};
+use crate::search::Evaluation;
+
+/// Under a clock, we don't start an iteration after this fraction of the optimum time, since it
+/// probably wouldn't finish in time.
+const START_ITERATION_FRACTION: f64 = 0.5;
+
+/// A score drop this large, in centistones, buys more time. Scores alternate with the side to move
+/// at the leaves, so we compare with the iteration two back.
+const SCORE_DROP: Evaluation = 30;
+
+/// Searches always get at least this long, even with an almost empty clock.
+const MIN_TIME_MS: i32 = 1;
+
+pub struct TimeManager {
+  start:             Instant,
+  optimum_ms:        i32,
+  maximum_ms:        i32,
+  /// With a fixed movetime we use all of it, and ignore stability.
+  fixed:             bool,
+  best_move:         Option<Move>,
+  /// How many iterations in a row have picked the same best move.
+  stable_iterations: u32,
+  scores:            Vec<Evaluation>,
+  score_dropped:     bool,
+}
+
+impl TimeManager {
+  /// Spends exactly `movetime_ms`, minus the move overhead.
+  pub fn fixed(movetime_ms: i32, move_overhead_ms: i32) -> TimeManager {
+    let ms = (movetime_ms - move_overhead_ms).max(MIN_TIME_MS);
+    TimeManager::with_times(ms, ms, true)
+  }
+
+  /// Budgets time from our clock. Without `moves_to_go` we guess how long the game will last from
+  /// how full the board is.
+  pub fn for_clock(
+    state: &State,
+    ms_on_clock: i32,
+    ms_increment: i32,
+    moves_to_go: Option<u32>,
+    move_overhead_ms: i32,
+  ) -> TimeManager {
+    let empty = (ALL_CELLS_MASK & !(state.black_stones | state.white_stones | state.gaps))
+      .count_ones() as i32;
+    let moves_left = match moves_to_go {
+      Some(moves_to_go) => moves_to_go.max(1) as i32,
+      // A clone fills one cell and a jump none, and the opponent fills about as many as we do.
+      None => (empty / 2 + 5).min(40),
+    };
+    // Whatever the clock says, keep the move overhead in reserve. GUIs that send a tiny clock
+    // with a big increment get a near instant move rather than a loss on time.
+    let usable = (ms_on_clock - move_overhead_ms).max(MIN_TIME_MS);
+    let optimum = (usable / moves_left + ms_increment.max(0) * 3 / 4).min(usable / 2);
+    let maximum = (optimum * 3).min(usable / 2);
+    TimeManager::with_times(
+      optimum.max(MIN_TIME_MS),
+      maximum.max(optimum).max(MIN_TIME_MS),
+      false,
+    )
+  }
+
+  fn with_times(optimum_ms: i32, maximum_ms: i32, fixed: bool) -> TimeManager {
+    TimeManager {
+      start: Instant::now(),
+      optimum_ms,
+      maximum_ms,
+      fixed,
+      best_move: None,
+      stable_iterations: 0,
+      scores: Vec::new(),
+      score_dropped: false,
+    }
+  }
+
+  pub fn optimum_ms(&self) -> i32 {
+    self.optimum_ms
+  }
+
+  pub fn maximum_ms(&self) -> i32 {
+    self.maximum_ms
+  }
+
+  pub fn elapsed_ms(&self) -> u64 {
+    self.start.elapsed().as_millis() as u64
+  }
+
+  /// The search must stop here, finished or not.
+  pub fn hard_deadline(&self) -> Instant {
+    self.start + Duration::from_millis(self.maximum_ms as u64)
+  }
+
+  /// Learns from an iteration's result how settled the search is.
+  pub fn update(&mut self, best_move: Move, score: Evaluation) {
+    match self.best_move == Some(best_move) {
+      true => self.stable_iterations += 1,
+      false => self.stable_iterations = 0,
+    }
+    self.score_dropped = match self.scores.len() {
+      0 | 1 => false,
+      n => score <= self.scores[n - 2] - SCORE_DROP,
+    };
+    self.best_move = Some(best_move);
+    self.scores.push(score);
+  }
+
+  /// The optimum time, scaled by how settled the search is.
+  pub fn adjusted_optimum_ms(&self) -> f64 {
+    if self.fixed {
+      return self.optimum_ms as f64;
+    }
+    let stability = match self.stable_iterations {
+      0 => 1.4,
+      1 => 1.1,
+      2 | 3 => 0.9,
+      _ => 0.7,
+    };
+    let score_drop = match self.score_dropped {
+      true => 1.3,
+      false => 1.0,
+    };
+    (self.optimum_ms as f64 * stability * score_drop).min(self.maximum_ms as f64)
+  }
+
+  pub fn should_start_iteration(&self) -> bool {
+    let fraction = match self.fixed {
+      true => 1.0,
+      false => START_ITERATION_FRACTION,
+    };
+    (self.elapsed_ms() as f64) < fraction * self.adjusted_optimum_ms()
+  }
+}
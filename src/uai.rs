@@ -0,0 +1,408 @@
+//! The UAI protocol, Ataxx's version of UCI: the commands a GUI sends, the messages an engine
+//! answers with, and parsing and formatting of both.
+
+pub mod client;
+
+use std::fmt;
+
+use crate::options::UaiOption;
+use crate::rules::{Move, State};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommandError {
+  /// The protocol says to ignore commands we don't know.
+  Unknown,
+  /// A known command we couldn't make sense of, which an engine reports to the GUI.
+  Malformed(String),
+}
+
+/// A search score from the point of view of the side to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+  /// In centistones.
+  Cp(i32),
+  /// A forced win in this many moves, or a loss if negative.
+  Mate(i32),
+}
+
+/// The fields of an info line. Fields the engine didn't send are None.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Info {
+  pub depth:    Option<u16>,
+  pub seldepth: Option<u16>,
+  pub multipv:  Option<u32>,
+  pub score:    Option<Score>,
+  /// Wins, draws and losses per thousand games.
+  pub wdl:      Option<[u32; 3]>,
+  pub nodes:    Option<u64>,
+  pub nps:      Option<u64>,
+  pub time_ms:  Option<u64>,
+  pub pv:       Vec<Move>,
+  /// Free text, which takes up the rest of the line.
+  pub string:   Option<String>,
+}
+
+impl Info {
+  /// Parses the arguments of an info line, after `info`. Fields we don't know are skipped.
+  pub fn parse(args: &[&str]) -> Result<Info, String> {
+    fn value<T: std::str::FromStr>(args: &[&str], i: usize) -> Result<Option<T>, String> {
+      match args.get(i + 1).map(|v| v.parse()) {
+        Some(Ok(v)) => Ok(Some(v)),
+        _ => Err(format!("Missing or invalid value for info {}", args[i])),
+      }
+    }
+    let mut info = Info::default();
+    let mut i = 0;
+    while i < args.len() {
+      match args[i] {
+        "depth" => info.depth = value(args, i)?,
+        "seldepth" => info.seldepth = value(args, i)?,
+        "multipv" => info.multipv = value(args, i)?,
+        "nodes" => info.nodes = value(args, i)?,
+        "nps" => info.nps = value(args, i)?,
+        "time" => info.time_ms = value(args, i)?,
+        "score" => {
+          i += 1;
+          info.score = match args.get(i) {
+            Some(&"cp") => value(args, i)?.map(Score::Cp),
+            Some(&"mate") => value(args, i)?.map(Score::Mate),
+            _ => return Err("Expected cp or mate after info score".to_string()),
+          };
+        }
+        "wdl" => {
+          let mut wdl = [0; 3];
+          for count in &mut wdl {
+            i += 1;
+            *count = match args.get(i).map(|v| v.parse()) {
+              Some(Ok(v)) => v,
+              _ => return Err("Expected three numbers after info wdl".to_string()),
+            };
+          }
+          info.wdl = Some(wdl);
+        }
+        // The PV runs until something that isn't a move.
+        "pv" => {
+          while let Some(m) = args.get(i + 1).and_then(|m| Move::from_uai(m).ok()) {
+            info.pv.push(m);
+            i += 1;
+          }
+        }
+        "string" => {
+          info.string = Some(args[i + 1..].join(" "));
+          break;
+        }
+        _ => {}
+      }
+      i += 1;
+    }
+    Ok(info)
+  }
+}
+
+/// The limits of a go command. Limits that aren't given are None.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GoParams {
+  pub ponder:    bool,
+  pub wtime:     Option<i32>,
+  pub btime:     Option<i32>,
+  pub winc:      Option<i32>,
+  pub binc:      Option<i32>,
+  pub movestogo: Option<u32>,
+  pub depth:     Option<u16>,
+  pub nodes:     Option<u64>,
+  pub movetime:  Option<i32>,
+  pub infinite:  bool,
+  /// Our own extension, which counts moves to this depth instead of searching.
+  pub perft:     Option<u16>,
+}
+
+impl GoParams {
+  /// Parses the arguments of a go command, after `go`. Limits we don't know are skipped.
+  pub fn parse(args: &[&str]) -> Result<GoParams, String> {
+    fn value<T: std::str::FromStr>(args: &[&str], i: usize) -> Result<Option<T>, String> {
+      match args.get(i + 1).map(|v| v.parse()) {
+        Some(Ok(v)) => Ok(Some(v)),
+        _ => Err(format!("Missing or invalid value for go {}", args[i])),
+      }
+    }
+    let mut go = GoParams::default();
+    for i in 0..args.len() {
+      match args[i] {
+        "ponder" => go.ponder = true,
+        "wtime" => go.wtime = value(args, i)?,
+        "btime" => go.btime = value(args, i)?,
+        "winc" => go.winc = value(args, i)?,
+        "binc" => go.binc = value(args, i)?,
+        "movestogo" => go.movestogo = value(args, i)?,
+        "depth" => go.depth = value(args, i)?,
+        "nodes" => go.nodes = value(args, i)?,
+        "movetime" => go.movetime = value(args, i)?,
+        "infinite" => go.infinite = true,
+        "perft" => go.perft = value(args, i)?,
+        _ => (),
+      }
+    }
+    Ok(go)
+  }
+}
+
+/// The arguments of a position command: a start position, and the moves played from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Position {
+  /// None for `startpos`. Kept as sent, so the command formats back the same way.
+  pub fen:   Option<String>,
+  pub moves: Vec<Move>,
+}
+
+impl Position {
+  /// Parses the arguments of a position command, `fen <fen> [moves ...]` or
+  /// `startpos [moves ...]`.
+  pub fn parse(args: &[&str]) -> Result<Position, String> {
+    let moves_index = args.iter().position(|t| *t == "moves").unwrap_or(args.len());
+    let fen = match args.first() {
+      Some(&"startpos") if moves_index == 1 => None,
+      Some(&"fen") if moves_index > 1 => {
+        let fen = args[1..moves_index].join(" ");
+        State::from_fen(&fen)?;
+        Some(fen)
+      }
+      _ => return Err(format!("Malformed position command: {}", args.join(" "))),
+    };
+    let moves =
+      args.iter().skip(moves_index + 1).map(|m| Move::from_uai(m)).collect::<Result<_, _>>()?;
+    Ok(Position { fen, moves })
+  }
+
+  /// Plays the moves from the start position, checking that every move is legal.
+  pub fn state(&self) -> Result<State, String> {
+    let mut state = match &self.fen {
+      Some(fen) => State::from_fen(fen)?,
+      None => State::startpos(),
+    };
+    for &m in &self.moves {
+      if !state.is_legal(m) {
+        return Err(format!("Illegal move: {}", m.to_uai()));
+      }
+      state.make_move(m).unwrap();
+    }
+    Ok(state)
+  }
+}
+
+/// What a GUI sends to an engine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GuiCommand {
+  Uai,
+  IsReady,
+  /// Names and values may contain spaces. Buttons are set without a value.
+  SetOption {
+    name:  String,
+    value: Option<String>,
+  },
+  UaiNewGame,
+  Position(Position),
+  Go(GoParams),
+  Stop,
+  PonderHit,
+  Quit,
+  /// Our own extension, which searches the bench positions, optionally to a given depth.
+  Bench(Option<u16>),
+  /// Our own extension, `dbg`, which draws the board.
+  Debug,
+}
+
+pub fn parse_command(line: &str) -> Result<GuiCommand, CommandError> {
+  let tokens = line.split_whitespace().collect::<Vec<_>>();
+  let malformed = |e: String| CommandError::Malformed(e);
+  match tokens.first() {
+    Some(&"uai") => Ok(GuiCommand::Uai),
+    Some(&"isready") => Ok(GuiCommand::IsReady),
+    Some(&"setoption") => {
+      if tokens.get(1) != Some(&"name") {
+        return Err(malformed(format!("Expected setoption name ...: {}", line)));
+      }
+      let value_index = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+      Ok(GuiCommand::SetOption {
+        name:  tokens[2..value_index].join(" "),
+        value: tokens.get(value_index + 1..).map(|value| value.join(" ")),
+      })
+    }
+    Some(&"uainewgame") => Ok(GuiCommand::UaiNewGame),
+    Some(&"position") => Position::parse(&tokens[1..]).map(GuiCommand::Position).map_err(malformed),
+    Some(&"go") => GoParams::parse(&tokens[1..]).map(GuiCommand::Go).map_err(malformed),
+    Some(&"stop") => Ok(GuiCommand::Stop),
+    Some(&"ponderhit") => Ok(GuiCommand::PonderHit),
+    Some(&"quit") => Ok(GuiCommand::Quit),
+    Some(&"bench") => match tokens.get(1).map(|d| d.parse()) {
+      None => Ok(GuiCommand::Bench(None)),
+      Some(Ok(depth)) => Ok(GuiCommand::Bench(Some(depth))),
+      Some(Err(_)) => Err(malformed(format!("Invalid bench depth: {}", tokens[1]))),
+    },
+    Some(&"dbg") => Ok(GuiCommand::Debug),
+    _ => Err(CommandError::Unknown),
+  }
+}
+
+impl fmt::Display for GuiCommand {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      GuiCommand::Uai => write!(f, "uai"),
+      GuiCommand::IsReady => write!(f, "isready"),
+      GuiCommand::SetOption { name, value } => {
+        write!(f, "setoption name {}", name)?;
+        match value {
+          Some(value) => write!(f, " value {}", value),
+          None => Ok(()),
+        }
+      }
+      GuiCommand::UaiNewGame => write!(f, "uainewgame"),
+      GuiCommand::Position(position) => {
+        match &position.fen {
+          Some(fen) => write!(f, "position fen {}", fen)?,
+          None => write!(f, "position startpos")?,
+        }
+        if !position.moves.is_empty() {
+          write!(f, " moves")?;
+        }
+        position.moves.iter().try_for_each(|m| write!(f, " {}", m.to_uai()))
+      }
+      GuiCommand::Go(go) => {
+        write!(f, "go")?;
+        if go.ponder {
+          write!(f, " ponder")?;
+        }
+        let limits = [
+          ("wtime", go.wtime.map(|v| v as i64)),
+          ("btime", go.btime.map(|v| v as i64)),
+          ("winc", go.winc.map(|v| v as i64)),
+          ("binc", go.binc.map(|v| v as i64)),
+          ("movestogo", go.movestogo.map(|v| v as i64)),
+          ("depth", go.depth.map(|v| v as i64)),
+          ("nodes", go.nodes.map(|v| v as i64)),
+          ("movetime", go.movetime.map(|v| v as i64)),
+        ];
+        for (name, value) in limits {
+          if let Some(value) = value {
+            write!(f, " {} {}", name, value)?;
+          }
+        }
+        if go.infinite {
+          write!(f, " infinite")?;
+        }
+        match go.perft {
+          Some(depth) => write!(f, " perft {}", depth),
+          None => Ok(()),
+        }
+      }
+      GuiCommand::Stop => write!(f, "stop"),
+      GuiCommand::PonderHit => write!(f, "ponderhit"),
+      GuiCommand::Quit => write!(f, "quit"),
+      GuiCommand::Bench(None) => write!(f, "bench"),
+      GuiCommand::Bench(Some(depth)) => write!(f, "bench {}", depth),
+      GuiCommand::Debug => write!(f, "dbg"),
+    }
+  }
+}
+
+/// What an engine sends to a GUI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EngineMessage {
+  IdName(String),
+  IdAuthor(String),
+  UaiOk,
+  ReadyOk,
+  Option(UaiOption),
+  Info(Info),
+  /// `0000` when the game is over and there's nothing to play.
+  BestMove {
+    m:      Move,
+    ponder: Option<Move>,
+  },
+}
+
+pub fn parse_message(line: &str) -> Result<EngineMessage, CommandError> {
+  let tokens = line.split_whitespace().collect::<Vec<_>>();
+  let malformed = |e: String| CommandError::Malformed(e);
+  match tokens.first() {
+    Some(&"id") => match tokens.get(1) {
+      Some(&"name") => Ok(EngineMessage::IdName(tokens[2..].join(" "))),
+      Some(&"author") => Ok(EngineMessage::IdAuthor(tokens[2..].join(" "))),
+      _ => Err(malformed(format!(
+        "Expected id name or id author: {}",
+        line
+      ))),
+    },
+    Some(&"uaiok") => Ok(EngineMessage::UaiOk),
+    Some(&"readyok") => Ok(EngineMessage::ReadyOk),
+    Some(&"option") => UaiOption::parse(&tokens[1..]).map(EngineMessage::Option).map_err(malformed),
+    Some(&"info") => Info::parse(&tokens[1..]).map(EngineMessage::Info).map_err(malformed),
+    Some(&"bestmove") => {
+      let m = tokens.get(1).ok_or_else(|| malformed(format!("Malformed bestmove: {}", line)));
+      let m = Move::from_uai(m?).map_err(malformed)?;
+      let ponder = match tokens.get(2..4) {
+        Some(["ponder", ponder]) => Some(Move::from_uai(ponder).map_err(malformed)?),
+        _ => None,
+      };
+      Ok(EngineMessage::BestMove { m, ponder })
+    }
+    _ => Err(CommandError::Unknown),
+  }
+}
+
+impl fmt::Display for EngineMessage {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      EngineMessage::IdName(name) => write!(f, "id name {}", name),
+      EngineMessage::IdAuthor(author) => write!(f, "id author {}", author),
+      EngineMessage::UaiOk => write!(f, "uaiok"),
+      EngineMessage::ReadyOk => write!(f, "readyok"),
+      EngineMessage::Option(option) => write!(f, "{}", option),
+      EngineMessage::Info(info) => {
+        write!(f, "info")?;
+        let fields = [
+          ("depth", info.depth.map(|v| v as u64)),
+          ("seldepth", info.seldepth.map(|v| v as u64)),
+          ("multipv", info.multipv.map(|v| v as u64)),
+        ];
+        for (name, value) in fields {
+          if let Some(value) = value {
+            write!(f, " {} {}", name, value)?;
+          }
+        }
+        match info.score {
+          Some(Score::Cp(score)) => write!(f, " score cp {}", score)?,
+          Some(Score::Mate(moves)) => write!(f, " score mate {}", moves)?,
+          None => {}
+        }
+        if let Some([wins, draws, losses]) = info.wdl {
+          write!(f, " wdl {} {} {}", wins, draws, losses)?;
+        }
+        // The order engines usually send these in.
+        for (name, value) in [
+          ("nodes", info.nodes),
+          ("time", info.time_ms),
+          ("nps", info.nps),
+        ] {
+          if let Some(value) = value {
+            write!(f, " {} {}", name, value)?;
+          }
+        }
+        if !info.pv.is_empty() {
+          write!(f, " pv")?;
+        }
+        info.pv.iter().try_for_each(|m| write!(f, " {}", m.to_uai()))?;
+        match &info.string {
+          Some(string) => write!(f, " string {}", string),
+          None => Ok(()),
+        }
+      }
+      EngineMessage::BestMove { m, ponder } => {
+        write!(f, "bestmove {}", m.to_uai())?;
+        match ponder {
+          Some(ponder) => write!(f, " ponder {}", ponder.to_uai()),
+          None => Ok(()),
+        }
+      }
+    }
+  }
+}
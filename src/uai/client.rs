@@ -0,0 +1,224 @@
+//! The GUI's side of the protocol: running an engine executable and talking to it.
+
+use std::io::{BufRead, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use super::{parse_message, EngineMessage, GoParams, GuiCommand, Info, Position};
+use crate::options::UaiOption;
+use crate::rules::{Move, State};
+
+/// How long an engine gets to answer `uai` and `isready`.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClientError {
+  /// The executable couldn't be started.
+  Spawn(String),
+  /// The engine didn't answer in time.
+  Timeout,
+  /// The engine exited, or closed its end of a pipe.
+  Disconnected,
+  /// The engine said something we couldn't make sense of.
+  Protocol(String),
+}
+
+impl std::fmt::Display for ClientError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      ClientError::Spawn(e) => write!(f, "Couldn't start the engine: {}", e),
+      ClientError::Timeout => write!(f, "The engine didn't answer in time"),
+      ClientError::Disconnected => write!(f, "The engine disconnected"),
+      ClientError::Protocol(e) => write!(f, "Protocol error: {}", e),
+    }
+  }
+}
+
+/// What a go command got back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchResult {
+  pub best_move: Move,
+  pub ponder:    Option<Move>,
+  /// Every info line the engine sent during the search, in order.
+  pub infos:     Vec<Info>,
+}
+
+impl SearchResult {
+  /// The last info line with a score, which for most engines is the deepest iteration.
+  pub fn last_scored_info(&self) -> Option<&Info> {
+    self.infos.iter().rev().find(|info| info.score.is_some())
+  }
+}
+
+/// A running engine, talked to over its stdin and stdout. Dropping it asks the engine to quit,
+/// and kills it if it doesn't.
+pub struct EngineProcess {
+  child:       Child,
+  stdin:       ChildStdin,
+  lines:       Receiver<String>,
+  /// Whether the engine owes us a best move, from a search `go` stopped waiting for.
+  searching:   bool,
+  /// From `id name` and `id author`.
+  pub name:    Option<String>,
+  pub author:  Option<String>,
+  /// The options the engine declared.
+  pub options: Vec<UaiOption>,
+}
+
+impl EngineProcess {
+  /// Starts the engine, and waits for it to answer `uai` and `isready`.
+  pub fn start(program: &str, args: &[String]) -> Result<EngineProcess, ClientError> {
+    let mut child = Command::new(program)
+      .args(args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .spawn()
+      .map_err(|e| ClientError::Spawn(format!("{}: {}", program, e)))?;
+    let stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    // Reading happens on its own thread, so waiting for a reply can time out.
+    let (sender, lines) = mpsc::channel();
+    std::thread::spawn(move || {
+      for line in std::io::BufReader::new(stdout).lines().map_while(Result::ok) {
+        if sender.send(line).is_err() {
+          break;
+        }
+      }
+    });
+    let mut engine = EngineProcess {
+      child,
+      stdin,
+      lines,
+      searching: false,
+      name: None,
+      author: None,
+      options: Vec::new(),
+    };
+    engine.send("uai")?;
+    for line in engine.read_until("uaiok", HANDSHAKE_TIMEOUT)? {
+      match parse_message(&line) {
+        Ok(EngineMessage::IdName(name)) => engine.name = Some(name),
+        Ok(EngineMessage::IdAuthor(author)) => engine.author = Some(author),
+        Ok(EngineMessage::Option(option)) => engine.options.push(option),
+        _ => {}
+      }
+    }
+    engine.is_ready()?;
+    Ok(engine)
+  }
+
+  pub fn send(&mut self, line: &str) -> Result<(), ClientError> {
+    writeln!(self.stdin, "{}", line).map_err(|_| ClientError::Disconnected)
+  }
+
+  pub fn send_command(&mut self, command: &GuiCommand) -> Result<(), ClientError> {
+    self.send(&command.to_string())
+  }
+
+  pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), ClientError> {
+    self.send_command(&GuiCommand::SetOption {
+      name:  name.to_string(),
+      value: Some(value.to_string()),
+    })
+  }
+
+  /// Waits until the engine has dealt with everything sent so far.
+  pub fn is_ready(&mut self) -> Result<(), ClientError> {
+    self.send_command(&GuiCommand::IsReady)?;
+    self.read_until("readyok", HANDSHAKE_TIMEOUT).map(|_| ())
+  }
+
+  pub fn new_game(&mut self) -> Result<(), ClientError> {
+    self.send_command(&GuiCommand::UaiNewGame)?;
+    self.is_ready()
+  }
+
+  pub fn set_position(&mut self, start: &State, moves: &[Move]) -> Result<(), ClientError> {
+    self.send_command(&GuiCommand::Position(Position {
+      fen:   (*start != State::startpos()).then(|| start.to_fen()),
+      moves: moves.to_vec(),
+    }))
+  }
+
+  /// Starts a search, and waits up to `timeout` for its best move. After a timeout the engine is
+  /// still searching, so it should be stopped or dropped. A search still running from an earlier
+  /// timeout is stopped first.
+  pub fn go(&mut self, params: &GoParams, timeout: Duration) -> Result<SearchResult, ClientError> {
+    if self.searching {
+      self.stop()?;
+    }
+    self.send_command(&GuiCommand::Go(params.clone()))?;
+    self.searching = true;
+    let lines = self.read_until("bestmove", timeout)?;
+    self.searching = false;
+    let (bestmove, rest) = lines.split_last().unwrap();
+    let (best_move, ponder) = match parse_message(bestmove) {
+      Ok(EngineMessage::BestMove { m, ponder }) => (m, ponder),
+      _ => {
+        return Err(ClientError::Protocol(format!(
+          "Malformed bestmove: {}",
+          bestmove
+        )))
+      }
+    };
+    // Engines write all sorts of info lines, so we skip the ones we can't read.
+    let infos = rest
+      .iter()
+      .filter_map(|line| match parse_message(line) {
+        Ok(EngineMessage::Info(info)) => Some(info),
+        _ => None,
+      })
+      .collect();
+    Ok(SearchResult {
+      best_move,
+      ponder,
+      infos,
+    })
+  }
+
+  /// Stops the search. The best move of a search `go` timed out on is read and thrown away, so
+  /// it isn't taken for the answer to the next one.
+  pub fn stop(&mut self) -> Result<(), ClientError> {
+    self.send_command(&GuiCommand::Stop)?;
+    if self.searching {
+      self.read_until("bestmove", HANDSHAKE_TIMEOUT)?;
+      self.searching = false;
+    }
+    Ok(())
+  }
+
+  /// Returns the lines up to and including the first one that starts with `command`.
+  fn read_until(&self, command: &str, timeout: Duration) -> Result<Vec<String>, ClientError> {
+    let deadline = Instant::now() + timeout;
+    let mut lines = Vec::new();
+    loop {
+      let timeout = deadline.saturating_duration_since(Instant::now());
+      let line = self.lines.recv_timeout(timeout).map_err(|e| match e {
+        RecvTimeoutError::Timeout => ClientError::Timeout,
+        RecvTimeoutError::Disconnected => ClientError::Disconnected,
+      })?;
+      let done = line.split_whitespace().next() == Some(command);
+      lines.push(line);
+      if done {
+        return Ok(lines);
+      }
+    }
+  }
+}
+
+impl Drop for EngineProcess {
+  fn drop(&mut self) {
+    let _ = self.send_command(&GuiCommand::Quit);
+    // Give the engine a moment to exit by itself.
+    for _ in 0..50 {
+      if let Ok(Some(_)) = self.child.try_wait() {
+        return;
+      }
+      std::thread::sleep(Duration::from_millis(10));
+    }
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
@@ -0,0 +1,135 @@
+use snpataxx::book::{Book, BookBuilder, BookEntry};
+use snpataxx::game::GameRecord;
+use snpataxx::rng::Rng;
+use snpataxx::rules::{Move, State};
+
+/// Black moves first, and 0-1 is a win for Black.
+const GAMES: &str = "\
+[Event \"1\"]
+[Result \"0-1\"]
+
+1. g2 a2 0-1
+
+[Event \"2\"]
+[Result \"0-1\"]
+
+1. g2 b2 0-1
+
+[Event \"3\"]
+[Result \"1/2-1/2\"]
+
+1. f2 a2 1/2-1/2
+
+[Event \"4\"]
+[Result \"*\"]
+
+1. g2 a2 *
+";
+
+fn builder(depth: usize) -> BookBuilder {
+  let mut builder = BookBuilder::new(depth);
+  let added = GameRecord::parse_all(GAMES)
+    .unwrap()
+    .iter()
+    .map(|game| builder.add_game(game).unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(added, [true, true, true, false]);
+  builder
+}
+
+fn mv(uai: &str) -> Move {
+  Move::from_uai(uai).unwrap()
+}
+
+#[test]
+fn builds_from_games() {
+  let book = builder(1).build(1);
+  let start = State::startpos();
+  let moves = book.moves(&start);
+  let g2 = moves.iter().find(|entry| entry.m == mv("g2")).unwrap();
+  assert_eq!((g2.wins, g2.draws, g2.losses, g2.weight), (2, 0, 0, 4));
+  let f2 = moves.iter().find(|entry| entry.m == mv("f2")).unwrap();
+  assert_eq!((f2.wins, f2.draws, f2.losses, f2.weight), (0, 1, 0, 1));
+  assert_eq!(book.len(), 2);
+}
+
+#[test]
+fn filters_by_games() {
+  let book = builder(2).build(2);
+  assert_eq!(book.len(), 1);
+  assert_eq!(book.entries()[0].m, mv("g2"));
+}
+
+#[test]
+fn picks_by_weight() {
+  let book = builder(2).build(1);
+  let rng = Rng::new(1);
+  let start = State::startpos();
+  let picks = (0..1000).map(|_| book.pick(&start, &rng).unwrap()).collect::<Vec<_>>();
+  let g2 = picks.iter().filter(|m| **m == mv("g2")).count();
+  assert_eq!(g2 + picks.iter().filter(|m| **m == mv("f2")).count(), 1000);
+  assert!((700..900).contains(&g2), "{}", g2);
+
+  // White's replies to g2 only ever lost, so they have no weight.
+  let mut state = start.clone();
+  state.make_move(mv("g2")).unwrap();
+  assert_eq!(book.moves(&state).len(), 2);
+  assert_eq!(book.pick(&state, &rng), None);
+}
+
+#[test]
+fn skips_moves_that_are_illegal_here() {
+  let start = State::startpos();
+  let entry = |m| BookEntry {
+    hash: start.hash_with_gaps(),
+    m,
+    weight: 1,
+    wins: 1,
+    draws: 0,
+    losses: 0,
+  };
+  // A hash collision with another position would look like this.
+  let book = Book::new(vec![entry(mv("d4")), entry(mv("g2"))]);
+  assert_eq!(book.moves(&start).len(), 1);
+  assert_eq!(book.pick(&start, &Rng::new(0)), Some(mv("g2")));
+}
+
+#[test]
+fn keeps_gap_layouts_apart() {
+  // The same stones and moves, on boards with different gaps.
+  let games = "\
+[FEN \"x5o/7/3-3/7/3-3/7/o5x x 0 1\"]
+[Result \"0-1\"]
+
+1. g2 0-1
+
+[FEN \"x5o/7/2-1-2/7/2-1-2/7/o5x x 0 1\"]
+[Result \"1-0\"]
+
+1. g2 1-0
+";
+  let mut builder = BookBuilder::new(1);
+  for game in GameRecord::parse_all(games).unwrap() {
+    assert!(builder.add_game(&game).unwrap());
+  }
+  let book = builder.build(1);
+  assert_eq!(book.len(), 2);
+  let won = State::from_fen("x5o/7/3-3/7/3-3/7/o5x x 0 1").unwrap();
+  let lost = State::from_fen("x5o/7/2-1-2/7/2-1-2/7/o5x x 0 1").unwrap();
+  let g2 = |state: &State| {
+    let moves = book.moves(state);
+    assert_eq!(moves.len(), 1);
+    (moves[0].wins, moves[0].losses)
+  };
+  assert_eq!(g2(&won), (1, 0));
+  assert_eq!(g2(&lost), (0, 1));
+  assert_eq!(book.pick(&lost, &Rng::new(0)), None);
+}
+
+#[test]
+fn round_trips_bytes() {
+  let book = builder(2).build(1);
+  let bytes = book.to_bytes();
+  assert_eq!(Book::from_bytes(&bytes).unwrap(), book);
+  assert!(Book::from_bytes(&bytes[1..]).is_err());
+}
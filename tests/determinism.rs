@@ -0,0 +1,42 @@
+use snpataxx::rules::State;
+use snpataxx::search::Engine;
+
+const FENS: [&str; 3] = [
+  "x5o/7/7/7/7/7/o5x x 0 1",
+  "x5o/7/2-1-2/7/2-1-2/7/o5x o 0 1",
+  "7/1xo4/1oox3/2xxo2/7/3o3/x5o x 0 1",
+];
+
+fn search(seed: u64, noise: u32, fen: &str) -> (i32, String) {
+  let mut engine = Engine::new(seed);
+  engine.noise = noise;
+  engine.set_position(State::from_fen(fen).unwrap());
+  let (score, m) = engine.run_depth(4);
+  (score, m.unwrap().to_uai())
+}
+
+#[test]
+fn without_noise_the_seed_doesnt_matter() {
+  for fen in FENS {
+    let expected = search(1, 0, fen);
+    for seed in [2, 3, 12345] {
+      assert_eq!(search(seed, 0, fen), expected, "{}", fen);
+    }
+  }
+}
+
+#[test]
+fn noise_is_reproducible_from_the_seed() {
+  for fen in FENS {
+    assert_eq!(search(7, 15, fen), search(7, 15, fen), "{}", fen);
+  }
+}
+
+#[test]
+fn clearing_restarts_the_noise() {
+  let mut engine = Engine::new(99);
+  engine.set_position(State::startpos());
+  let first = engine.run_depth(4);
+  engine.clear();
+  assert_eq!(engine.run_depth(4), first);
+}
@@ -0,0 +1,111 @@
+use snpataxx::game::{GameRecord, RecordedMove};
+use snpataxx::rules::{GameResult, Move, State};
+
+/// A game as cutechess-cli writes it, with tags we don't interpret and comments of every kind.
+const CUTECHESS_PGN: &str = r#"[Event "Gauntlet"]
+[Site "?"]
+[Date "2024.03.09"]
+[Round "1"]
+[White "engine-b"]
+[Black "engine-a"]
+[Result "0-1"]
+[FEN "x5o/7/7/7/7/7/o5x x 0 1"]
+[GameDuration "00:00:01"]
+[PlyCount "5"]
+[SetUp "1"]
+[TimeControl "8+0.08"]
+[Variant "ataxx"]
+
+1. g2 {book} a2 {-0.12/7 0.05s} 2. f2 {+1.04/8 0.06s} b2 {-M3/5 0.01s}
+3. a7c5 {+M2/4 0.01s, Black wins by adjudication} 0-1
+"#;
+
+#[test]
+fn parses_cutechess_games() {
+  let record = GameRecord::parse(CUTECHESS_PGN).unwrap();
+  assert_eq!(record.event, "Gauntlet");
+  assert_eq!(record.date, "2024.03.09");
+  assert_eq!(record.black, "engine-a");
+  assert_eq!(record.white, "engine-b");
+  assert_eq!(record.result, Some(GameResult::BlackWins));
+  assert_eq!(record.time_control.as_deref(), Some("8+0.08"));
+  assert_eq!(record.other_tags.len(), 6);
+  let scores = record.moves.iter().map(|m| (m.score, m.depth)).collect::<Vec<_>>();
+  assert_eq!(
+    scores,
+    [
+      (None, None),
+      (Some(-12), Some(7)),
+      (Some(104), Some(8)),
+      (Some(-999_997), Some(5)),
+      (Some(999_998), Some(4)),
+    ]
+  );
+  let positions = record.replay().unwrap();
+  assert_eq!(positions.len(), 6);
+  assert_eq!(positions[5].to_fen(), "6o/7/2x4/7/7/oo3xx/o5x o 0 1");
+}
+
+#[test]
+fn round_trips() {
+  let moves = ["g2", "a2", "f2", "b2", "a7c5", "0000"];
+  let record = GameRecord {
+    event:        "Round \"trip\"".to_string(),
+    date:         "2024.03.09".to_string(),
+    black:        "snpataxx".to_string(),
+    white:        "snpataxx\\dev".to_string(),
+    fen:          Some("x5o/7/2-1-2/7/2-1-2/7/o5x o 0 1".to_string()),
+    time_control: Some("40/60".to_string()),
+    other_tags:   vec![("Site".to_string(), "here".to_string())],
+    moves:        (0..)
+      .zip(moves)
+      .map(|(i, m)| RecordedMove {
+        m:     Move::from_uai(m).unwrap(),
+        score: (i % 2 == 0).then_some(i * 37 - 50),
+        depth: (i % 4 == 0).then_some(i as u16 + 3),
+      })
+      .collect(),
+    result:       Some(GameResult::Draw),
+  };
+  let pgn = record.to_pgn();
+  assert!(pgn.contains("1... g2 {-0.50/3} 2. a2"), "{}", pgn);
+  assert_eq!(GameRecord::parse(&pgn).unwrap(), record);
+
+  let unfinished = GameRecord::default();
+  assert_eq!(GameRecord::parse(&unfinished.to_pgn()).unwrap(), unfinished);
+
+  let both = format!("{}\n{}", pgn, unfinished.to_pgn());
+  assert_eq!(GameRecord::parse_all(&both).unwrap(), [record, unfinished]);
+}
+
+#[test]
+fn replay_rejects_illegal_moves() {
+  let record = GameRecord::parse("1. g2 g3 *").unwrap();
+  assert_eq!(record.replay().err().unwrap(), "Illegal move g3 at ply 2");
+
+  let record = GameRecord::parse(
+    r#"[FEN "7/7/7/7/7/7/7 x 0 1"]
+
+1. 0000 *"#,
+  )
+  .unwrap();
+  assert!(record.replay().is_err());
+}
+
+#[test]
+fn rejects_mismatched_results() {
+  assert!(GameRecord::parse("[Result \"1-0\"]\n\n1. g2 0-1").is_err());
+  assert!(GameRecord::parse("1. g2 0-1 a2").is_err());
+}
+
+#[test]
+fn fen_round_trips() {
+  for fen in [
+    "x5o/7/7/7/7/7/o5x x 0 1",
+    "x5o/7/2-1-2/3-3/2-1-2/7/o5x o 0 1",
+    "ooxxxxx/ooxxx2/1oooxo1/2ooxo1/7/7/o5x x 0 1",
+    "7/7/7/7/7/7/7 x 0 1",
+  ] {
+    assert_eq!(State::from_fen(fen).unwrap().to_fen(), fen);
+  }
+}
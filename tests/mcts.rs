@@ -0,0 +1,47 @@
+use std::sync::{Arc, Mutex};
+
+use snpataxx::mcts::Mcts;
+use snpataxx::rules::State;
+use snpataxx::search::{SearchEvent, SearchLimits, Searcher};
+
+/// Searches `state` with a playout limit, and returns what the observer heard.
+fn search(state: State, playouts: u64) -> Vec<SearchEvent> {
+  let events = Arc::new(Mutex::new(Vec::new()));
+  let sink = events.clone();
+  let mut mcts = Mcts::new(1);
+  mcts.set_observer(Some(Box::new(move |event: &SearchEvent| {
+    sink.lock().unwrap().push(event.clone())
+  })));
+  mcts.set_position(state);
+  mcts.search(&SearchLimits {
+    nodes: Some(playouts),
+    ..SearchLimits::default()
+  });
+  let events = events.lock().unwrap().clone();
+  events
+}
+
+#[test]
+fn reports_playouts_and_odds() {
+  let events = search(State::startpos(), 500);
+  let Some(SearchEvent::IterationDone(info)) = events.iter().rev().nth(1) else {
+    panic!("no iteration before the end");
+  };
+  assert_eq!(info.nodes, 500);
+  let [wins, draws, losses] = info.lines[0].wdl.unwrap();
+  assert_eq!(wins + draws + losses, 1000);
+  let Some(SearchEvent::Finished(stats)) = events.last() else {
+    panic!("no end of search");
+  };
+  assert_eq!(stats.nodes, 500);
+  assert_eq!(stats.best_move, Some(info.lines[0].pv[0]));
+}
+
+#[test]
+fn finishes_a_finished_game() {
+  let events = search(State::from_fen("x6/7/7/7/7/7/6x o 0 1").unwrap(), 500);
+  let Some(SearchEvent::Finished(stats)) = events.last() else {
+    panic!("no end of search");
+  };
+  assert_eq!(stats.best_move, None);
+}
@@ -0,0 +1,25 @@
+#![cfg(feature = "nnue")]
+
+use snpataxx::nnue::{Accumulator, Network, INPUTS};
+use snpataxx::rules::State;
+
+#[test]
+fn big_networks_do_not_overflow() {
+  // Every activation clips to its maximum and meets the largest output weight, which sums past
+  // i32::MAX with this many hidden units.
+  let hidden = 20_000;
+  let mut bytes = b"SNPNNUE1".to_vec();
+  bytes.extend((hidden as u32).to_le_bytes());
+  bytes.extend(std::iter::repeat_n(0u8, 2 * INPUTS * hidden));
+  bytes.extend(std::iter::repeat_n(1000i16.to_le_bytes(), hidden).flatten());
+  bytes.extend(std::iter::repeat_n(127u8, 2 * hidden));
+  bytes.extend(0i32.to_le_bytes());
+  let network = Network::from_bytes(&bytes).unwrap();
+  let state = State::startpos();
+  let accumulator = Accumulator::refresh(&network, &state);
+  let expected = 2 * hidden as i64 * 255 * 127 * 400 / (255 * 64);
+  assert_eq!(
+    network.evaluate(&accumulator, state.to_move) as i64,
+    expected
+  );
+}
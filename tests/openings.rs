@@ -0,0 +1,78 @@
+use snpataxx::openings::{self, symmetric_gaps, OpeningConfig, Symmetry};
+use snpataxx::rng::Rng;
+use snpataxx::rules::State;
+
+#[test]
+fn mirrors_the_corners() {
+  let start = State::startpos();
+  assert_eq!(
+    Symmetry::Horizontal.apply(start.black_stones),
+    start.white_stones
+  );
+  assert_eq!(
+    Symmetry::Vertical.apply(start.black_stones),
+    start.white_stones
+  );
+  assert_eq!(
+    Symmetry::Rotational.apply(start.black_stones),
+    start.black_stones
+  );
+  let board = State::from_fen("x1-2--/-6/2o4/7/3x3/5-1/o-5 x 0 1").unwrap().gaps;
+  for symmetry in Symmetry::ALL {
+    assert_eq!(symmetry.apply(symmetry.apply(board)), board);
+    assert_eq!(symmetry.apply(board).count_ones(), board.count_ones());
+  }
+}
+
+#[test]
+fn places_symmetric_gaps() {
+  let start = State::startpos();
+  let rng = Rng::new(7);
+  for symmetry in Symmetry::ALL {
+    for max_gaps in [0, 1, 4, 9, 20] {
+      let gaps = symmetric_gaps(symmetry, max_gaps, &rng);
+      assert_eq!(symmetry.apply(gaps), gaps);
+      assert!(gaps.count_ones() <= max_gaps);
+      assert_eq!(gaps & (start.black_stones | start.white_stones), 0);
+    }
+  }
+}
+
+#[test]
+fn generates_distinct_openings() {
+  let config = OpeningConfig {
+    symmetries: vec![Symmetry::Horizontal],
+    min_gaps: 4,
+    ..OpeningConfig::default()
+  };
+  let generated = openings::generate(&config, 5, 1);
+  assert_eq!(generated.len(), 5);
+  for (i, state) in generated.iter().enumerate() {
+    assert_eq!(state.black_stones, State::startpos().black_stones);
+    assert_eq!(Symmetry::Horizontal.apply(state.gaps), state.gaps);
+    assert!((4..=8).contains(&state.gaps.count_ones()));
+    assert!(generated[..i].iter().all(|other| other.to_fen() != state.to_fen()));
+  }
+
+  let config = OpeningConfig {
+    random_plies: 2,
+    ..OpeningConfig::default()
+  };
+  let generated = openings::generate(&config, 3, 2);
+  assert_eq!(generated.len(), 3);
+  for state in generated {
+    assert_ne!(state.black_stones, State::startpos().black_stones);
+    assert_ne!(state.white_stones, State::startpos().white_stones);
+  }
+}
+
+#[test]
+fn saves_and_loads() {
+  let generated = openings::generate(&OpeningConfig::default(), 3, 3);
+  let path = std::env::temp_dir().join("snpataxx-openings-test.epd");
+  let path = path.to_str().unwrap();
+  openings::save(path, &generated).unwrap();
+  let loaded = openings::load(path).unwrap();
+  std::fs::remove_file(path).unwrap();
+  assert!(loaded == generated);
+}
@@ -0,0 +1,91 @@
+use snpataxx::perft::{divide, perft, PerftCache};
+use snpataxx::rules::State;
+
+/// Positions and their perft counts from depth 1 up.
+const POSITIONS: [(&str, &[u64]); 9] = [
+  // The start position, from either side.
+  ("x5o/7/7/7/7/7/o5x x 0 1", &[16, 256, 6460, 155888]),
+  ("x5o/7/7/7/7/7/o5x o 0 1", &[16, 256, 6460, 155888]),
+  // Gaps.
+  ("x5o/7/2-1-2/7/2-1-2/7/o5x x 0 1", &[14, 196, 4184, 86528]),
+  ("x5o/7/2-1-2/3-3/2-1-2/7/o5x x 0 1", &[14, 196, 4100, 83104]),
+  ("x5o/7/3-3/2-1-2/3-3/7/o5x x 0 1", &[16, 256, 5948, 133264]),
+  // The side to move is walled in and has to pass.
+  (
+    "7/7/7/7/ooooooo/ooooooo/xxxxxxx x 0 1",
+    &[1, 75, 249, 14270],
+  ),
+  (
+    "7/7/7/7/xxxxxxx/xxxxxxx/ooooooo o 0 1",
+    &[1, 75, 249, 14270],
+  ),
+  // Black is boxed in by gaps, so only jumps are possible.
+  ("x-5/--5/7/7/7/7/6o x 0 1", &[5, 40, 599, 10031]),
+  // No stones at all, so the game is over.
+  ("7/7/7/7/7/7/7 x 0 1", &[0, 0, 0, 0]),
+];
+
+#[test]
+fn known_counts() {
+  for (fen, counts) in POSITIONS {
+    let state = State::from_fen(fen).unwrap();
+    for (depth, expected) in (1..).zip(counts.iter()) {
+      assert_eq!(
+        perft(&state, depth, None),
+        *expected,
+        "{} at depth {}",
+        fen,
+        depth
+      );
+    }
+  }
+}
+
+#[test]
+fn hashed_counts_match() {
+  let cache = PerftCache::new(1);
+  for (fen, counts) in POSITIONS {
+    let state = State::from_fen(fen).unwrap();
+    // Later depths reuse entries from earlier ones.
+    for (depth, expected) in (1..).zip(counts.iter()) {
+      assert_eq!(
+        perft(&state, depth, Some(&cache)),
+        *expected,
+        "{} at depth {}",
+        fen,
+        depth
+      );
+    }
+  }
+}
+
+#[test]
+fn divide_sums_to_perft() {
+  let cache = PerftCache::new(1);
+  for (fen, counts) in POSITIONS {
+    let state = State::from_fen(fen).unwrap();
+    let depth = counts.len() as u16;
+    let split = divide(&state, depth, 3, Some(&cache));
+    assert_eq!(
+      split.iter().map(|(_, count)| count).sum::<u64>(),
+      counts[depth as usize - 1]
+    );
+    for (m, count) in split {
+      let mut child = state.clone();
+      child.make_move(m).unwrap();
+      assert_eq!(
+        perft(&child, depth - 1, None),
+        count,
+        "{} after {}",
+        fen,
+        m.to_uai()
+      );
+    }
+  }
+}
+
+#[test]
+fn startpos_depth_5() {
+  let cache = PerftCache::new(16);
+  assert_eq!(perft(&State::startpos(), 5, Some(&cache)), 4752668);
+}
@@ -0,0 +1,37 @@
+use snpataxx::rules::{Color, GameResult, State};
+
+#[test]
+fn games_go_on_while_both_sides_have_stones() {
+  let state = State::from_fen("x5o/7/7/7/7/7/o5x x 0 1").unwrap();
+  assert!(!state.game_is_over());
+  assert_eq!(state.result(), None);
+  assert!(state.get_winner().is_none());
+}
+
+#[test]
+fn a_side_without_stones_loses() {
+  // Plenty of empty cells, but White has nothing left to move.
+  let state = State::from_fen("x6/7/7/7/7/7/6x o 0 1").unwrap();
+  assert!(state.game_is_over());
+  assert_eq!(state.result(), Some(GameResult::BlackWins));
+  assert!(state.get_winner() == Some(Color::Black));
+}
+
+#[test]
+fn a_full_board_with_equal_stones_is_a_draw() {
+  // 24 stones each and one gap.
+  let state =
+    State::from_fen("xxxxxxx/ooooooo/xxxxxxx/ooooooo/xxxxxxx/ooooooo/xxx-ooo x 0 1").unwrap();
+  assert!(state.game_is_over());
+  assert_eq!(state.result(), Some(GameResult::Draw));
+  assert_eq!(state.result_by_material(), GameResult::Draw);
+  assert!(state.get_winner().is_none());
+}
+
+#[test]
+fn only_the_gap_aware_hash_sees_gaps() {
+  let open = State::from_fen("x5o/7/7/7/7/7/o5x x 0 1").unwrap();
+  let gapped = State::from_fen("x5o/7/2-1-2/7/2-1-2/7/o5x x 0 1").unwrap();
+  assert_eq!(open.get_hash(), gapped.get_hash());
+  assert_ne!(open.hash_with_gaps(), gapped.hash_with_gaps());
+}
@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+
+use snpataxx::rules::State;
+use snpataxx::search::{Engine, SearchLimits};
+
+fn timed_search(engine: &mut Engine, limits: &SearchLimits) -> Duration {
+  let start = Instant::now();
+  let (_, m) = engine.run(limits);
+  assert!(m.is_some());
+  start.elapsed()
+}
+
+#[test]
+fn back_to_back_searches_get_their_own_movetime() {
+  let mut engine = Engine::new(1);
+  engine.set_position(State::startpos());
+
+  // A search that finishes long before its movetime must not leave anything behind that stops
+  // the next one.
+  let quick = SearchLimits {
+    depth: Some(1),
+    movetime_ms: Some(50),
+    ..SearchLimits::default()
+  };
+  assert!(timed_search(&mut engine, &quick) < Duration::from_millis(50));
+
+  let long = SearchLimits {
+    movetime_ms: Some(300),
+    ..SearchLimits::default()
+  };
+  let elapsed = timed_search(&mut engine, &long);
+  assert!(
+    elapsed >= Duration::from_millis(300),
+    "stopped after {:?}",
+    elapsed
+  );
+  assert!(
+    elapsed < Duration::from_millis(1000),
+    "stopped after {:?}",
+    elapsed
+  );
+
+  let short = SearchLimits {
+    movetime_ms: Some(30),
+    ..SearchLimits::default()
+  };
+  let elapsed = timed_search(&mut engine, &short);
+  assert!(
+    elapsed >= Duration::from_millis(30),
+    "stopped after {:?}",
+    elapsed
+  );
+  assert!(
+    elapsed < Duration::from_millis(500),
+    "stopped after {:?}",
+    elapsed
+  );
+}
@@ -0,0 +1,94 @@
+use std::sync::{Arc, Mutex};
+
+use snpataxx::rng::Rng;
+use snpataxx::rules::State;
+use snpataxx::search::{Engine, SearchEvent, SearchLimits, SearchLine, Searcher};
+use snpataxx::skill::{blunder, pick_line, skill_for_elo, MAX_SKILL_LEVEL, SKILL_ELO};
+
+#[test]
+fn every_level_has_its_own_elo() {
+  assert!(SKILL_ELO.windows(2).all(|pair| pair[0] < pair[1]));
+  for level in 0..=MAX_SKILL_LEVEL {
+    let elo = SKILL_ELO[level as usize];
+    assert_eq!(skill_for_elo(elo), level);
+    assert_eq!(skill_for_elo(elo + 1), level);
+  }
+  assert_eq!(skill_for_elo(0), 0);
+  assert_eq!(skill_for_elo(i32::MAX), MAX_SKILL_LEVEL);
+}
+
+/// How many centistones a level gives away on average, choosing between these lines.
+fn average_loss(level: u8) -> f64 {
+  let lines = [100, 60, 20, -20]
+    .map(|score| SearchLine {
+      depth: 5,
+      score,
+      wdl: None,
+      pv: Vec::new(),
+    })
+    .to_vec();
+  let rng = Rng::new(level as u64);
+  let samples = 4000;
+  let lost = (0..samples)
+    .map(|_| lines[0].score - lines[pick_line(&lines, level, &rng)].score)
+    .sum::<i32>();
+  lost as f64 / samples as f64
+}
+
+#[test]
+fn low_levels_pick_worse_lines() {
+  let losses = [0, 5, 10, 15, 18, 20].map(average_loss);
+  assert!(
+    losses.windows(2).all(|pair| pair[0] > pair[1]),
+    "{:?}",
+    losses
+  );
+  assert_eq!(losses[5], 0.0);
+}
+
+#[test]
+fn lines_from_an_older_iteration_are_not_picked() {
+  let lines = [(6, 10), (5, 90), (5, 80)]
+    .map(|(depth, score)| SearchLine {
+      depth,
+      score,
+      wdl: None,
+      pv: Vec::new(),
+    })
+    .to_vec();
+  let rng = Rng::new(0);
+  assert!((0..100).all(|_| pick_line(&lines, 0, &rng) == 0));
+}
+
+#[test]
+fn only_low_levels_blunder() {
+  let state = State::startpos();
+  let rng = Rng::new(0);
+  let blunders = |level| (0..1000).filter(|_| blunder(&state, level, &rng).is_some()).count();
+  assert!((700..900).contains(&blunders(0)));
+  assert!(blunders(4) < blunders(0));
+  assert_eq!(blunders(10), 0);
+  assert_eq!(blunders(MAX_SKILL_LEVEL), 0);
+}
+
+#[test]
+fn weak_levels_only_report_multipv_lines() {
+  let reported = Arc::new(Mutex::new(Vec::new()));
+  let mut engine = Engine::new(1);
+  engine.skill_level = 5;
+  engine.set_position(State::startpos());
+  let sink = reported.clone();
+  engine.set_observer(Some(Box::new(move |event: &SearchEvent| {
+    if let SearchEvent::IterationDone(info) = event {
+      sink.lock().unwrap().push(info.lines.len());
+    }
+  })));
+  engine.search(&SearchLimits {
+    depth: Some(3),
+    ..SearchLimits::default()
+  });
+  let reported = reported.lock().unwrap();
+  assert!(!reported.is_empty());
+  assert!(reported.iter().all(|&lines| lines == 1));
+  assert_eq!(engine.lines().len(), 1);
+}
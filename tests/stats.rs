@@ -0,0 +1,129 @@
+use snpataxx::stats::{
+  elo_to_score, score_to_elo, Pentanomial, Sprt, SprtResult, Trinomial, MAX_ELO,
+};
+
+fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+  assert!(
+    (actual - expected).abs() <= tolerance,
+    "{} is not within {} of {}",
+    actual,
+    tolerance,
+    expected
+  );
+}
+
+#[test]
+fn elo_and_score_convert() {
+  assert_close(elo_to_score(0.0), 0.5, 1e-12);
+  assert_close(score_to_elo(0.75), 190.85, 0.01);
+  for elo in [-400.0, -35.0, 0.0, 5.0, 250.0] {
+    assert_close(score_to_elo(elo_to_score(elo)), elo, 1e-9);
+  }
+}
+
+#[test]
+fn trinomial_elo() {
+  let even = Trinomial {
+    wins:   30,
+    losses: 30,
+    draws:  40,
+  };
+  let estimate = even.elo();
+  assert_close(estimate.elo, 0.0, 1e-6);
+  assert_close(estimate.upper, -estimate.lower, 1e-6);
+  // 100 games with 60% of the points, and a variance of 0.19 per game.
+  let better = Trinomial {
+    wins:   50,
+    losses: 30,
+    draws:  20,
+  };
+  let estimate = better.elo();
+  assert_close(better.score(), 0.6, 1e-12);
+  assert_close(estimate.elo, 70.44, 0.01);
+  assert_close(
+    estimate.lower,
+    score_to_elo(0.6 - 1.959964 * 0.0019f64.sqrt()),
+    0.01,
+  );
+}
+
+#[test]
+fn pentanomial_counts_pairs() {
+  let mut pentanomial = Pentanomial::default();
+  pentanomial.add_pair(1.0, 0.0);
+  pentanomial.add_pair(1.0, 0.5);
+  pentanomial.add_pair(0.5, 0.5);
+  pentanomial.add_pair(1.0, 1.0);
+  assert_eq!(pentanomial.counts, [0, 0, 2, 1, 1]);
+  assert_eq!(pentanomial.pairs(), 4);
+}
+
+#[test]
+fn pairs_tighten_error_bars() {
+  // Each pair is one win and one loss, as when every opening favours one side.
+  let pentanomial = Pentanomial {
+    counts: [5, 0, 90, 0, 5],
+  };
+  let trinomial = Trinomial {
+    wins:   100,
+    losses: 100,
+    draws:  0,
+  };
+  assert_close(pentanomial.elo().elo, 0.0, 1e-6);
+  assert!(pentanomial.elo().error() < trinomial.elo().error() / 2.0);
+}
+
+#[test]
+fn perfect_scores_have_finite_error_bars() {
+  // Four pairs won twice each.
+  let pentanomial = Pentanomial {
+    counts: [0, 0, 0, 0, 4],
+  };
+  let estimate = pentanomial.elo();
+  assert!(estimate.elo > 0.0 && estimate.elo <= MAX_ELO);
+  assert_eq!(estimate.upper, MAX_ELO);
+  assert!(estimate.error().is_finite());
+  let estimate = Trinomial {
+    wins:   0,
+    losses: 8,
+    draws:  0,
+  }
+  .elo();
+  assert_eq!(estimate.lower, -MAX_ELO);
+  assert!(estimate.error().is_finite());
+}
+
+#[test]
+fn sprt_decides() {
+  let sprt = Sprt::default();
+  let (lower, upper) = sprt.bounds();
+  assert_close(lower, -2.944, 0.001);
+  assert_close(upper, 2.944, 0.001);
+  assert_eq!(Pentanomial::default().llr(&sprt), 0.0);
+
+  let elo0 = Sprt {
+    elo0: 0.0,
+    elo1: 20.0,
+    ..sprt
+  };
+  // Clearly better, clearly worse, and about halfway between the hypotheses.
+  let better = Pentanomial {
+    counts: [20, 150, 500, 250, 80],
+  };
+  let worse = Pentanomial {
+    counts: [80, 250, 500, 150, 20],
+  };
+  let unclear = Pentanomial {
+    counts: [3, 15, 50, 17, 4],
+  };
+  assert_eq!(elo0.result(better.llr(&elo0)), SprtResult::AcceptH1);
+  assert_eq!(elo0.result(worse.llr(&elo0)), SprtResult::AcceptH0);
+  assert_eq!(elo0.result(unclear.llr(&elo0)), SprtResult::Continue);
+  // Swapping the hypotheses flips the ratio.
+  let swapped = Sprt {
+    elo0: 20.0,
+    elo1: 0.0,
+    ..sprt
+  };
+  assert_close(better.llr(&swapped), -better.llr(&elo0), 1e-6);
+}
@@ -0,0 +1,86 @@
+use snpataxx::rules::{Move, State};
+use snpataxx::timeman::TimeManager;
+
+fn mv(uai: &str) -> Move {
+  Move::from_uai(uai).unwrap()
+}
+
+#[test]
+fn budgets_from_the_clock() {
+  let start = State::startpos();
+  // The start position has 45 empty cells, so about 27 moves left.
+  let guessed = TimeManager::for_clock(&start, 10_000, 0, None, 0);
+  assert_eq!(guessed.optimum_ms(), 10_000 / 27);
+  assert_eq!(guessed.maximum_ms(), 3 * (10_000 / 27));
+
+  let counted = TimeManager::for_clock(&start, 10_000, 0, Some(5), 0);
+  assert_eq!(counted.optimum_ms(), 2000);
+  assert_eq!(counted.maximum_ms(), 5000);
+
+  // Most of the increment gets spent.
+  let incremented = TimeManager::for_clock(&start, 10_000, 400, Some(5), 0);
+  assert_eq!(incremented.optimum_ms(), 2300);
+}
+
+#[test]
+fn never_budgets_nothing() {
+  let start = State::startpos();
+  for (clock, increment) in [(5, 0), (10, 0), (0, 0), (-50, 0), (5, 1000)] {
+    let time = TimeManager::for_clock(&start, clock, increment, None, 10);
+    assert!(time.optimum_ms() > 0, "clock {}", clock);
+    assert!(time.maximum_ms() >= time.optimum_ms(), "clock {}", clock);
+  }
+  let fixed = TimeManager::fixed(5, 10);
+  assert!(fixed.optimum_ms() > 0);
+}
+
+#[test]
+fn keeps_half_the_clock() {
+  let start = State::startpos();
+  for clock in [40, 100, 1000, 60_000] {
+    for moves_to_go in [None, Some(1), Some(2), Some(30)] {
+      for increment in [0, 100, 5000] {
+        let time = TimeManager::for_clock(&start, clock, increment, moves_to_go, 10);
+        let usable = clock - 10;
+        assert!(
+          time.maximum_ms() <= usable / 2,
+          "clock {} increment {} moves to go {:?}: maximum {}",
+          clock,
+          increment,
+          moves_to_go,
+          time.maximum_ms()
+        );
+      }
+    }
+  }
+}
+
+#[test]
+fn stability_scales_the_optimum() {
+  let mut time = TimeManager::for_clock(&State::startpos(), 10_000, 0, None, 0);
+  let optimum = time.optimum_ms() as f64;
+  let mut adjusted = Vec::new();
+  for _ in 0..5 {
+    time.update(mv("g2"), 100);
+    adjusted.push(time.adjusted_optimum_ms());
+  }
+  // The first iteration has nothing to agree with, so it counts as a change.
+  assert!(adjusted[0] > optimum);
+  assert!(adjusted.windows(2).all(|pair| pair[1] <= pair[0]));
+  assert!(adjusted[4] < optimum);
+
+  time.update(mv("f2"), 100);
+  assert!(time.adjusted_optimum_ms() > optimum);
+  assert!(time.adjusted_optimum_ms() <= time.maximum_ms() as f64);
+}
+
+#[test]
+fn starts_iterations_while_there_is_time() {
+  let time = TimeManager::for_clock(&State::startpos(), 10_000, 0, None, 0);
+  assert!(time.should_start_iteration());
+
+  let time = TimeManager::fixed(20, 0);
+  assert!(time.should_start_iteration());
+  std::thread::sleep(std::time::Duration::from_millis(30));
+  assert!(!time.should_start_iteration());
+}
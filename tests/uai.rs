@@ -0,0 +1,173 @@
+use snpataxx::options::{OptionType, UaiOption};
+use snpataxx::rules::{Move, State};
+use snpataxx::uai::{
+  parse_command, parse_message, CommandError, EngineMessage, GoParams, GuiCommand, Info, Position,
+  Score,
+};
+
+/// What a GUI sent over a game, with a few of the commands it didn't get to use.
+const GUI_TRANSCRIPT: &str = "\
+uai
+isready
+setoption name Hash value 64
+setoption name Debug Log File value <empty>
+setoption name Clear Hash
+uainewgame
+position startpos
+go wtime 10000 btime 10000 winc 100 binc 100
+position startpos moves g2 a1a3
+go wtime 9850 btime 9920 winc 100 binc 100 movestogo 38
+position fen x5o/7/2-1-2/7/2-1-2/7/o5x o 3 12 moves f7 0000
+go ponder wtime 9000 btime 9000
+ponderhit
+go depth 6 nodes 100000 movetime 500
+go infinite
+stop
+go perft 4
+bench 3
+dbg
+quit";
+
+/// What snpataxx answered to `uai` and `isready`, a depth 3 search of the start position with
+/// MultiPV set to 2, and a search of a finished game.
+const ENGINE_TRANSCRIPT: &str = "\
+id name snpataxx
+id author Peter Schmidt-Nielsen
+option name Hash type spin default 16 min 1 max 65536
+option name Threads type spin default 1 min 1 max 256
+option name MultiPV type spin default 1 min 1 max 256
+option name Ponder type check default false
+option name Move Overhead type spin default 10 min 0 max 5000
+option name Random Noise type spin default 15 min 0 max 1000
+option name Seed type spin default 0 min 0 max 2147483647
+option name Skill Level type spin default 20 min 0 max 20
+option name UAI_LimitStrength type check default false
+option name UAI_Elo type spin default 2647 min 258 max 2647
+option name Debug Log File type string default <empty>
+option name EvalFile type string default <empty>
+option name OwnBook type check default false
+option name BookFile type string default <empty>
+option name Search type combo default ab var ab var mcts var random var greedy var minimax1
+info string seed 8681791831469088901
+uaiok
+readyok
+info depth 1 multipv 1 score cp 100 nodes 31 time 0 pv g2
+info depth 1 multipv 2 score cp 96 nodes 31 time 0 pv b6
+info depth 2 multipv 1 score cp 2 nodes 571 time 0 pv f1 f6
+info depth 2 multipv 2 score cp 1 nodes 571 time 0 pv b7 b2
+info depth 3 multipv 1 score cp 98 nodes 6701 time 2 pv a6 b2 f2
+info depth 3 multipv 2 score cp 98 nodes 6701 time 2 pv b7 f7 c7
+info depth 3 nodes 6701 time 2 nps 3350500
+bestmove a6
+readyok
+info depth 0 nodes 0 time 0 nps 0
+bestmove 0000";
+
+/// Messages snpataxx never sends, but other engines do.
+const OTHER_ENGINE_MESSAGES: &str = "\
+option name Clear Hash type button
+info depth 9 seldepth 14 score mate -3 wdl 0 0 1000 nodes 5000 time 10 pv a1a3 0000 f7
+bestmove g2 ponder a1a3";
+
+#[test]
+fn gui_transcript_round_trips() {
+  for line in GUI_TRANSCRIPT.lines() {
+    let command = parse_command(line).unwrap_or_else(|e| panic!("{}: {:?}", line, e));
+    assert_eq!(command.to_string(), line);
+  }
+}
+
+#[test]
+fn engine_transcript_round_trips() {
+  for line in ENGINE_TRANSCRIPT.lines().chain(OTHER_ENGINE_MESSAGES.lines()) {
+    let message = parse_message(line).unwrap_or_else(|e| panic!("{}: {:?}", line, e));
+    assert_eq!(message.to_string(), line);
+  }
+}
+
+#[test]
+fn parses_commands() {
+  assert_eq!(
+    parse_command("setoption name Move Overhead value 30"),
+    Ok(GuiCommand::SetOption {
+      name:  "Move Overhead".to_string(),
+      value: Some("30".to_string()),
+    })
+  );
+  assert_eq!(
+    parse_command("go movestogo 10 btime 500 infinite"),
+    Ok(GuiCommand::Go(GoParams {
+      btime: Some(500),
+      movestogo: Some(10),
+      infinite: true,
+      ..GoParams::default()
+    }))
+  );
+  assert_eq!(parse_command("hello"), Err(CommandError::Unknown));
+  assert!(matches!(
+    parse_command("go depth many"),
+    Err(CommandError::Malformed(_))
+  ));
+  assert!(matches!(
+    parse_command("position fen x5o/7 x 0 1"),
+    Err(CommandError::Malformed(_))
+  ));
+}
+
+#[test]
+fn position_checks_legality() {
+  let GuiCommand::Position(position) = parse_command("position startpos moves g2 a2").unwrap()
+  else {
+    panic!("Not a position command");
+  };
+  let mut state = State::startpos();
+  state.make_move(Move::from_uai("g2").unwrap()).unwrap();
+  state.make_move(Move::from_uai("a2").unwrap()).unwrap();
+  assert!(position.state().unwrap() == state);
+
+  let position = Position {
+    fen:   None,
+    moves: vec![Move::from_uai("d4").unwrap()],
+  };
+  assert_eq!(position.state().err().unwrap(), "Illegal move: d4");
+}
+
+#[test]
+fn parses_messages() {
+  assert_eq!(
+    parse_message("info depth 2 score cp -15 pv g2 a2"),
+    Ok(EngineMessage::Info(Info {
+      depth: Some(2),
+      score: Some(Score::Cp(-15)),
+      pv: vec![Move::from_uai("g2").unwrap(), Move::from_uai("a2").unwrap()],
+      ..Info::default()
+    }))
+  );
+  assert_eq!(
+    parse_message("option name Search type combo default ab var ab var mcts"),
+    Ok(EngineMessage::Option(UaiOption::combo(
+      "Search",
+      "ab",
+      &["ab", "mcts"]
+    )))
+  );
+  let EngineMessage::Option(option) =
+    parse_message("option name Debug Log File type string default <empty>").unwrap()
+  else {
+    panic!("Not an option");
+  };
+  assert_eq!(
+    option.kind,
+    OptionType::String {
+      default: String::new(),
+    }
+  );
+  assert!(matches!(
+    parse_message("option name Hash type spin default 16"),
+    Err(CommandError::Malformed(_))
+  ));
+  assert!(matches!(
+    parse_message("bestmove"),
+    Err(CommandError::Malformed(_))
+  ));
+}
@@ -0,0 +1,183 @@
+use std::time::{Duration, Instant};
+
+use snpataxx::options::OptionType;
+use snpataxx::rules::{Move, State};
+use snpataxx::uai::client::{ClientError, EngineProcess};
+use snpataxx::uai::{GoParams, Info, Score};
+
+fn start() -> EngineProcess {
+  EngineProcess::start(env!("CARGO_BIN_EXE_snpataxx"), &[]).unwrap()
+}
+
+#[test]
+fn handshake() {
+  let engine = start();
+  assert_eq!(engine.name.as_deref(), Some("snpataxx"));
+  assert!(engine.author.is_some());
+  assert!(engine
+    .options
+    .iter()
+    .any(|option| option.name == "Hash" && matches!(option.kind, OptionType::Spin { .. })));
+}
+
+#[test]
+fn searches_to_a_depth() {
+  let mut engine = start();
+  engine.new_game().unwrap();
+  let moves = [Move::from_uai("g2").unwrap(), Move::from_uai("a2").unwrap()];
+  engine.set_position(&State::startpos(), &moves).unwrap();
+  let params = GoParams {
+    depth: Some(3),
+    ..GoParams::default()
+  };
+  let search = engine.go(&params, Duration::from_secs(30)).unwrap();
+
+  let mut state = State::startpos();
+  for m in moves {
+    state.make_move(m).unwrap();
+  }
+  assert!(state.is_legal(search.best_move));
+  let info = search.last_scored_info().unwrap();
+  assert_eq!(info.depth, Some(3));
+  assert!(matches!(info.score, Some(Score::Cp(_))));
+  assert!(info.nodes.unwrap() > 0);
+  assert_eq!(info.pv.first(), Some(&search.best_move));
+  // The last line sums up the search, with a speed.
+  assert!(search.infos.last().unwrap().nps.is_some());
+}
+
+#[test]
+fn options_change_the_search() {
+  let mut engine = start();
+  engine.set_option("MultiPV", "3").unwrap();
+  engine.is_ready().unwrap();
+  engine
+    .set_position(
+      &State::from_fen("x5o/7/2-1-2/7/2-1-2/7/o5x o 0 1").unwrap(),
+      &[],
+    )
+    .unwrap();
+  let params = GoParams {
+    depth: Some(2),
+    ..GoParams::default()
+  };
+  let search = engine.go(&params, Duration::from_secs(30)).unwrap();
+  let lines = search
+    .infos
+    .iter()
+    .filter(|info| info.depth == Some(2) && info.multipv.is_some())
+    .collect::<Vec<_>>();
+  assert_eq!(lines.len(), 3);
+  // Best first.
+  let scores = lines
+    .iter()
+    .map(|info| match info.score {
+      Some(Score::Cp(cp)) => cp,
+      score => panic!("unexpected score {:?}", score),
+    })
+    .collect::<Vec<_>>();
+  assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+}
+
+#[test]
+fn times_out() {
+  let mut engine = start();
+  engine.set_position(&State::startpos(), &[]).unwrap();
+  let params = GoParams {
+    movetime: Some(2000),
+    ..GoParams::default()
+  };
+  assert_eq!(
+    engine.go(&params, Duration::from_millis(100)),
+    Err(ClientError::Timeout)
+  );
+}
+
+#[test]
+fn stopping_drops_the_late_best_move() {
+  let mut engine = start();
+  engine.set_position(&State::startpos(), &[]).unwrap();
+  let params = GoParams {
+    movetime: Some(2000),
+    ..GoParams::default()
+  };
+  assert_eq!(
+    engine.go(&params, Duration::from_millis(100)),
+    Err(ClientError::Timeout)
+  );
+  engine.stop().unwrap();
+  // Nothing Black could play from the start position is legal for White here.
+  let state = State::from_fen("x5o/7/7/7/7/7/o5x o 0 1").unwrap();
+  engine.set_position(&state, &[]).unwrap();
+  let params = GoParams {
+    depth: Some(2),
+    ..GoParams::default()
+  };
+  let search = engine.go(&params, Duration::from_secs(30)).unwrap();
+  assert!(state.is_legal(search.best_move));
+  assert_eq!(search.last_scored_info().unwrap().depth, Some(2));
+}
+
+#[test]
+fn stop_ends_the_search() {
+  let mut engine = start();
+  engine.set_position(&State::startpos(), &[]).unwrap();
+  let params = GoParams {
+    movetime: Some(60_000),
+    ..GoParams::default()
+  };
+  assert_eq!(
+    engine.go(&params, Duration::from_millis(100)),
+    Err(ClientError::Timeout)
+  );
+  let start = Instant::now();
+  engine.stop().unwrap();
+  assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn infinite_searches_wait_for_stop() {
+  let mut engine = start();
+  engine.set_position(&State::startpos(), &[]).unwrap();
+  let params = GoParams {
+    infinite: true,
+    ..GoParams::default()
+  };
+  // Longer than any fixed time an unstoppable engine might fall back on.
+  assert_eq!(
+    engine.go(&params, Duration::from_millis(1500)),
+    Err(ClientError::Timeout)
+  );
+  // The engine still listens while it searches.
+  engine.is_ready().unwrap();
+  engine.stop().unwrap();
+}
+
+#[test]
+fn parses_info_lines() {
+  let line = "depth 7 seldepth 9 multipv 2 score mate -3 wdl 0 5 995 nodes 1234 nps 5000 time 250 pv g2 a1a3 \
+              hashfull 10";
+  let info = Info::parse(&line.split_whitespace().collect::<Vec<_>>()).unwrap();
+  assert_eq!(
+    info,
+    Info {
+      depth:    Some(7),
+      seldepth: Some(9),
+      multipv:  Some(2),
+      score:    Some(Score::Mate(-3)),
+      wdl:      Some([0, 5, 995]),
+      nodes:    Some(1234),
+      nps:      Some(5000),
+      time_ms:  Some(250),
+      pv:       vec![
+        Move::from_uai("g2").unwrap(),
+        Move::from_uai("a1a3").unwrap()
+      ],
+      string:   None,
+    }
+  );
+  let info = Info::parse(&["string", "seed", "42"]).unwrap();
+  assert_eq!(info.string.as_deref(), Some("seed 42"));
+  assert!(Info::parse(&["depth", "x"]).is_err());
+  assert!(Info::parse(&["wdl", "1", "2"]).is_err());
+}